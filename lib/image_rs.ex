@@ -1,6 +1,12 @@
 defmodule ImageRs do
   @moduledoc """
+  ## Errors
 
+  Invalid arguments checked on the Elixir side are returned as `{:error, message}`.
+  Errors raised by the NIFs are returned as
+  `{:error, {reason, %{format: format, message: message}}}`, see `t:error/0`.
+  `reason` is an atom such as `:enoent`, `:decoding_error`, `:unsupported_color_type`
+  or `:bad_argument`, and `format` the image format involved, if known.
   """
 
   defstruct [
@@ -10,6 +16,7 @@ defmodule ImageRs do
     color_type: nil,
     dtype: nil,
     shape: nil,
+    format: nil,
     resource: nil
   ]
 
@@ -17,12 +24,32 @@ defmodule ImageRs do
           resource: reference(),
           width: non_neg_integer(),
           height: non_neg_integer(),
-          color_type: :l | :la | :rgb | :rgba | :unknown,
+          color_type: :l | :la | :rgb | :rgba,
           dtype: :u8 | :u16 | :f32,
           shape: [non_neg_integer()],
-          channels: non_neg_integer()
+          channels: non_neg_integer(),
+          format: format() | nil
         }
 
+  @type error :: {atom(), %{format: format() | nil, message: String.t()}}
+
+  @type format ::
+          :png
+          | :jpeg
+          | :gif
+          | :webp
+          | :pnm
+          | :tiff
+          | :tga
+          | :dds
+          | :bmp
+          | :ico
+          | :hdr
+          | :exr
+          | :farbfeld
+          | :avif
+          | :qoi
+
   @type output_format ::
           :png
           | :jpeg
@@ -37,12 +64,25 @@ defmodule ImageRs do
           | :avif
           | :qoi
           | :webp
+          | :hdr
 
   @doc """
   Decode image from a given file
 
   - **filename**. Path to the image.
 
+  ## Options
+  - `:format`. Decode the image as this format instead of detecting it from the file
+    extension, e.g. `:tga`. Must be one of `t:format/0`.
+  - `:max_width`. Refuse to decode images wider than this, returns
+    `{:error, {:dimension_error, _}}`.
+  - `:max_height`. Refuse to decode images taller than this, returns
+    `{:error, {:dimension_error, _}}`.
+  - `:max_alloc`. Maximum number of bytes the decoder may allocate, returns
+    `{:error, {:insufficient_memory, _}}` when exceeded. Defaults to 512 MiB.
+  - `:auto_orient`. When `true`, rotate and flip the image according to its
+    EXIF orientation. Defaults to `false`.
+
   ## Example
   ```elixir
   {:ok, image} = ImageRs.from_file("/path/to/image")
@@ -53,23 +93,28 @@ defmodule ImageRs do
   {^height, ^width, ^channels} = shape
   color_type = image.color_type
   type = image.type
+  # the format the image was decoded from, e.g. :png
+  format = image.format
   ```
   """
-  @spec from_file(Path.t()) :: {:ok, ImageRs.t()} | {:error, String.t()}
-  def from_file(filename) do
-    ImageRs.Nif.from_file(filename)
+  @spec from_file(Path.t(), Keyword.t()) :: {:ok, ImageRs.t()} | {:error, String.t()}
+  def from_file(filename, options \\ []) do
+    with {:ok, format} <- validate_input_format(options[:format]),
+         {:ok, checked_options} <- validate_decode_options(options) do
+      ImageRs.Nif.from_file(filename, format, checked_options)
+    end
   end
 
   @doc """
-  Similar to from_file/1 but raises on errors
+  Similar to from_file/2 but raises on errors
   """
-  @spec from_file!(Path.t()) :: ImageRs.t()
-  def from_file!(filename) do
-    with {:ok, image} <- ImageRs.Nif.from_file(filename) do
+  @spec from_file!(Path.t(), Keyword.t()) :: ImageRs.t()
+  def from_file!(filename, options \\ []) do
+    with {:ok, image} <- from_file(filename, options) do
       image
     else
-      {:error, msg} ->
-        raise RuntimeError, msg
+      {:error, error} ->
+        raise RuntimeError, error_message(error)
     end
   end
 
@@ -78,6 +123,9 @@ defmodule ImageRs do
 
   - **data**. Image data in memory.
 
+  Accepts the same options as `from_file/2`. Without `:format`, the format is
+  detected from the content, which does not work for headerless formats such as TGA.
+
   ## Example
   ```elixir
   # image buffer from a file or perhaps download from the Internet
@@ -94,26 +142,154 @@ defmodule ImageRs do
   type = image.type
   ```
   """
-  @spec from_binary(binary()) :: {:ok, ImageRs.t()} | {:error, String.t()}
-  def from_binary(data) when is_binary(data) do
-    ImageRs.Nif.from_binary(data)
+  @spec from_binary(binary(), Keyword.t()) :: {:ok, ImageRs.t()} | {:error, String.t()}
+  def from_binary(data, options \\ []) when is_binary(data) do
+    with {:ok, format} <- validate_input_format(options[:format]),
+         {:ok, checked_options} <- validate_decode_options(options) do
+      ImageRs.Nif.from_binary(data, format, checked_options)
+    end
   end
 
   @doc """
-  Similar to from_binary/1 but raises on errors
+  Similar to from_binary/2 but raises on errors
   """
-  @spec from_binary!(binary()) :: ImageRs.t()
-  def from_binary!(data) when is_binary(data) do
-    with {:ok, image} <- ImageRs.Nif.from_binary(data) do
+  @spec from_binary!(binary(), Keyword.t()) :: ImageRs.t()
+  def from_binary!(data, options \\ []) when is_binary(data) do
+    with {:ok, image} <- from_binary(data, options) do
       image
     else
-      {:error, msg} ->
-        raise RuntimeError, msg
+      {:error, error} ->
+        raise RuntimeError, error_message(error)
+    end
+  end
+
+  @doc """
+  Decode all frames of an animated image from a given file
+
+  - **filename**. Path to the image.
+
+  Animated GIF, APNG and WebP images return one `ImageRs.Frame` per frame,
+  other images return a single frame.
+
+  ## Example
+  ```elixir
+  frames = ImageRs.frames_from_file("/path/to/animated.gif")
+  [%ImageRs.Frame{image: %ImageRs{}, delay: {numerator, denominator}} | _] = frames
+  ```
+  """
+  @spec frames_from_file(Path.t()) :: {:ok, [ImageRs.Frame.t()]} | {:error, String.t()}
+  def frames_from_file(filename) do
+    ImageRs.Nif.frames_from_file(filename)
+  end
+
+  @doc """
+  Decode all frames of an animated image from buffer in memory
+
+  - **data**. Image data in memory.
+
+  See `frames_from_file/1` for details.
+  """
+  @spec frames_from_binary(binary()) :: {:ok, [ImageRs.Frame.t()]} | {:error, String.t()}
+  def frames_from_binary(data) when is_binary(data) do
+    ImageRs.Nif.frames_from_binary(data)
+  end
+
+  @doc """
+  Decode all pages of a multi-page image from a given file
+
+  - **filename**. Path to the image.
+
+  Multi-page TIFF images return one `ImageRs` per page, each with its own size,
+  color type and ICC profile. Other images return a single page. The number of
+  pages is available without decoding through `probe_file/1`.
+
+  ## Options
+  - `:max_width`, `:max_height` and `:max_alloc`, as in `from_file/2`. The
+    dimension limits apply to each page, `:max_alloc` to all pages together.
+
+  ## Example
+  ```elixir
+  [first_page | _] = ImageRs.pages_from_file("/path/to/fax.tiff")
+  ```
+  """
+  @spec pages_from_file(Path.t(), Keyword.t()) :: {:ok, [ImageRs.t()]} | {:error, String.t()}
+  def pages_from_file(filename, options \\ []) do
+    with {:ok, checked_options} <- validate_decode_options(options) do
+      ImageRs.Nif.pages_from_file(filename, checked_options)
     end
   end
 
+  @doc """
+  Decode all pages of a multi-page image from buffer in memory
+
+  - **data**. Image data in memory.
+
+  See `pages_from_file/2` for details.
+  """
+  @spec pages_from_binary(binary(), Keyword.t()) :: {:ok, [ImageRs.t()]} | {:error, String.t()}
+  def pages_from_binary(data, options \\ []) when is_binary(data) do
+    with {:ok, checked_options} <- validate_decode_options(options) do
+      ImageRs.Nif.pages_from_binary(data, checked_options)
+    end
+  end
+
+  @doc """
+  Read format, dimensions and color type of an image file without decoding its pixels
+
+  - **filename**. Path to the image.
+
+  ## Example
+  ```elixir
+  %ImageRs.Info{format: :png, width: width, height: height} = ImageRs.probe_file("/path/to/image.png")
+  ```
+  """
+  @spec probe_file(Path.t()) :: {:ok, ImageRs.Info.t()} | {:error, String.t()}
+  def probe_file(filename) do
+    ImageRs.Nif.probe_file(filename)
+  end
+
+  @doc """
+  Read format, dimensions and color type of an image in memory without decoding its pixels
+
+  - **data**. Image data in memory.
+  """
+  @spec probe_binary(binary()) :: {:ok, ImageRs.Info.t()} | {:error, String.t()}
+  def probe_binary(data) when is_binary(data) do
+    ImageRs.Nif.probe_binary(data)
+  end
+
+  @doc """
+  Read EXIF metadata from an image file
+
+  - **filename**. Path to the image.
+
+  EXIF is read from JPEG, PNG, TIFF and WebP images.
+
+  ## Example
+  ```elixir
+  %ImageRs.Metadata{make: make, model: model} = ImageRs.metadata_from_file("/path/to/photo.jpg")
+  ```
+  """
+  @spec metadata_from_file(Path.t()) :: {:ok, ImageRs.Metadata.t()} | {:error, String.t()}
+  def metadata_from_file(filename) do
+    ImageRs.Nif.metadata_from_file(filename)
+  end
+
+  @doc """
+  Read EXIF metadata from an image in memory
+
+  - **data**. Image data in memory.
+  """
+  @spec metadata_from_binary(binary()) :: {:ok, ImageRs.Metadata.t()} | {:error, String.t()}
+  def metadata_from_binary(data) when is_binary(data) do
+    ImageRs.Nif.metadata_from_binary(data)
+  end
+
   @doc """
   Create a new `ImageRs` from given binary with corresponding parameters.
+
+  The `format` of the new image is `nil`. As there are no grayscale f32 images,
+  `:l` and `:la` with `:f32` create grayscale `:rgb` and `:rgba` images instead.
   """
   @spec new(pos_integer(), pos_integer(), :l | :la | :rgb | :rgba, :u8 | :u16 | :f32, binary()) ::
           {:ok, ImageRs.t()} | {:error, String.t()}
@@ -129,6 +305,30 @@ defmodule ImageRs do
     ImageRs.Nif.to_binary(image)
   end
 
+  @doc """
+  Get the ICC profile embedded in the source image, or `nil` if there is none.
+
+  The profile is kept by image ops that keep the image gray or color alike, and
+  is embedded again when encoding as `:png`, `:jpeg`, `:tiff` or `:webp`.
+  """
+  @spec icc_profile(ImageRs.t()) :: {:ok, binary() | nil} | {:error, String.t()}
+  def icc_profile(image) do
+    ImageRs.Nif.icc_profile(image)
+  end
+
+  @doc """
+  Convert the pixels of this image from its embedded ICC profile to sRGB.
+
+  RGB profiles (e.g. Display P3, Adobe RGB) apply to RGB(A) images and gray profiles
+  to gray images. CMYK images are already converted to RGB when decoded, so their
+  CMYK profiles return `{:error, {:unsupported_image_data, _}}`. The returned image
+  has no ICC profile. Images without an ICC profile are returned as they are.
+  """
+  @spec convert_to_srgb(ImageRs.t()) :: {:ok, ImageRs.t()} | {:error, String.t()}
+  def convert_to_srgb(image) do
+    ImageRs.Nif.convert_to_srgb(image)
+  end
+
   @doc """
   Resize this image using the specified filter algorithm.
 
@@ -207,6 +407,36 @@ defmodule ImageRs do
     ImageRs.Nif.grayscale(image)
   end
 
+  @doc """
+  Convert this image to another color type and data type.
+
+  Dropping the alpha channel discards it, adding one makes the image opaque.
+  Converting to `:l` or `:la` uses the same weights as `grayscale/1`, and as with
+  `new/5`, `:l` and `:la` with `:f32` give grayscale `:rgb` and `:rgba` images.
+  `:f32` samples are in `[0, 1]`, and are clamped to it when converted back to
+  `:u8` or `:u16`.
+
+  ## Example
+  ```elixir
+  # e.g. normalize uploads before feeding them to a model
+  image = ImageRs.convert(image, :rgb, :f32)
+  ```
+  """
+  @spec convert(ImageRs.t(), :l | :la | :rgb | :rgba, :u8 | :u16 | :f32) ::
+          {:ok, ImageRs.t()} | {:error, String.t() | error()}
+  def convert(image, color_type, dtype) do
+    cond do
+      color_type not in [:l, :la, :rgb, :rgba] ->
+        {:error, "`color_type` must be one of `:l`, `:la`, `:rgb` or `:rgba`"}
+
+      dtype not in [:u8, :u16, :f32] ->
+        {:error, "`dtype` must be one of `:u8`, `:u16` or `:f32`"}
+
+      true ->
+        ImageRs.Nif.convert(image, color_type, dtype)
+    end
+  end
+
   @doc """
   Invert the colors of this image.
   """
@@ -349,29 +579,232 @@ defmodule ImageRs do
     ImageRs.Nif.rotate270(image)
   end
 
+  @doc """
+  Rotate and flip this image according to an EXIF orientation.
+
+  `orientation` is the EXIF orientation value in `1..8`, as found in
+  `ImageRs.Metadata`. `1` returns the image unchanged.
+  """
+  @spec apply_orientation(ImageRs.t(), 1..8) ::
+          {:ok, ImageRs.t()} | {:error, String.t()}
+  def apply_orientation(image, orientation) when orientation in 1..8 do
+    ImageRs.Nif.apply_orientation(image, orientation)
+  end
+
+  def apply_orientation(_image, _orientation) do
+    {:error, "`orientation` must be an integer in [1, 8]"}
+  end
+
+  @doc """
+  Tone map a 32-bit float (HDR) RGB(A) image into an 8-bit image.
+
+  - **operator**. One of
+    - `:reinhard` (default), `x / (1 + x)`.
+    - `:aces_filmic`, an approximation of the ACES filmic curve with more contrast.
+    - `:exposure`, only applies exposure and gamma and clips highlights.
+
+  ## Options
+  - `:exposure`. Exposure adjustment in stops applied before the operator. Defaults to `0`.
+  - `:gamma`. Gamma used to encode the mapped values. Defaults to `2.2`.
+
+  The alpha channel, if any, is clamped to `[0, 1]`.
+  """
+  @spec tonemap(ImageRs.t(), :reinhard | :aces_filmic | :exposure, Keyword.t()) ::
+          {:ok, ImageRs.t()} | {:error, String.t()}
+  def tonemap(image, operator \\ :reinhard, options \\ []) do
+    exposure = Keyword.get(options, :exposure, 0)
+    gamma = Keyword.get(options, :gamma, 2.2)
+
+    cond do
+      operator not in [:reinhard, :aces_filmic, :exposure] ->
+        {:error, "`operator` must be one of `:reinhard`, `:aces_filmic` or `:exposure`"}
+
+      not is_number(exposure) ->
+        {:error, "`:exposure` must be a number"}
+
+      not (is_number(gamma) and gamma > 0) ->
+        {:error, "`:gamma` must be a positive number"}
+
+      true ->
+        ImageRs.Nif.tonemap(image, operator, exposure / 1, gamma / 1)
+    end
+  end
+
   @doc """
   Encode this image as format.
+
+  Images are converted to the nearest color type the format supports, e.g. 16-bit
+  and 32-bit float images are written as 8-bit for `:jpeg` and `:bmp` and as
+  16-bit for `:png`. `:exr` stores 32-bit float RGB(A) and `:farbfeld` 16-bit RGBA,
+  which hold all other color types losslessly.
+
+  Formats without an alpha channel, i.e. `:jpeg`, `:hdr` (Radiance) and `:pnm`
+  unless the subtype is `:arbitrarymap`, get the image flattened onto `:background`.
+
+  Failures of the encoder are returned as `{:error, error}`, see `t:error/0`, with
+  reasons like `:unsupported_color_type`, `:parameter_error` or `:encoding_error`.
+
+  ## Options
+  - `:background`. An `{r, g, b}` tuple of integers in `[0, 255]` that transparent
+    pixels are blended onto. Defaults to white, `{255, 255, 255}`.
+  - `:png`
+    - `:compression`. One of `:fast` (default), `:default` or `:best`.
+    - `:filter`. One of `:none`, `:sub`, `:up`, `:avg`, `:paeth` or `:adaptive` (default).
+  - `:jpeg`
+    - `:quality`. An integer in `[0, 100]`. Defaults to `75`.
+
+    The following options need the NIF to be built with the `jpeg-encoder` cargo
+    feature. Without it, only `subsampling: "4:4:4"`, `progressive: false` and
+    `optimize_huffman: false` are accepted, since that is what the built-in encoder
+    writes, and other values return `{:error, {:unsupported_format, _}}`.
+    - `:subsampling`. Chroma subsampling, one of `"4:4:4"`, `"4:2:2"` or `"4:2:0"`.
+      Defaults to `"4:2:0"` below quality 90 and `"4:4:4"` from 90.
+    - `:progressive`. Write a progressive JPEG. Defaults to `false`.
+    - `:restart_interval`. Number of MCUs between restart markers, in `[1, 65535]`.
+    - `:optimize_huffman`. Compute optimized Huffman tables. Defaults to `false`.
+  - `:webp`
+    - `:lossless`. `true` (default) or `false`. Lossy encoding needs the NIF to be
+      built with the `webp-lossy` cargo feature, and returns
+      `{:error, {:unsupported_format, _}}` otherwise.
+    - `:quality`. A number in `[0, 100]` used when `:lossless` is `false`. Defaults to `75`.
+  - `:avif`. Needs the NIF to be built with the `avif` cargo feature.
+    - `:speed`. An integer in `[1, 10]`, `1` is the slowest with the best compression,
+      `10` the fastest. Defaults to `4`.
+    - `:quality`. An integer in `[1, 100]`, also used for the alpha channel. Defaults to `80`.
+    - `:threads`. Number of encoder threads. Defaults to all threads of the `rayon` pool.
+  - `:tiff`
+    - `:compression`. One of `:none` (default), `:lzw`, `:deflate` or `:packbits`.
+  - `:pnm`
+    - `:subtype`. Required, one of `:bitmap`, `:graymap`, `:pixmap` or `:arbitrarymap`.
+    - `:encoding`. Required unless `:subtype` is `:arbitrarymap`, either `:binary` or `:ascii`.
   """
   @spec encode_as(ImageRs.t(), output_format(), Keyword.t()) ::
-          {:ok, binary()} | {:error, String.t()}
+          {:ok, binary()} | {:error, String.t() | error()}
   def encode_as(image, format, options \\ []) do
-    with {:ok, checked_options} <- validate_output_format_and_options(format, options) do
+    with {:ok, checked_options} <- validate_output_format_and_options(format, options),
+         {:ok, checked_options} <- validate_background(checked_options, options[:background]) do
       ImageRs.Nif.encode_as(image, format, checked_options)
     end
   end
 
+  @doc """
+  Encode a list of images as an animation.
+
+  - **images**. Frames of the animation, in order. All frames must have the same width
+    and height, otherwise `{:error, {:invalid_frame, _}}` is returned. Each frame is
+    converted to RGBA.
+  - **delays**. How long each frame is shown, either in milliseconds or as a
+    `{numerator, denominator}` fraction of milliseconds. Must have the same length as `images`.
+  - **format**. Only `:gif` is supported at the moment.
+
+  ## Options
+  - `:repeat`. `:infinite` (default) or the number of times the animation is repeated.
+  - `:speed`. GIF encoder speed in `1..30`. `1` (default) gives the best quality,
+    `30` the fastest encoding.
+
+  ## Example
+  ```elixir
+  frames = ImageRs.frames_from_file("/path/to/animated.gif")
+  images = Enum.map(frames, & &1.image)
+  delays = Enum.map(frames, & &1.delay)
+  gif = ImageRs.encode_animation(images, delays, :gif, repeat: 3, speed: 10)
+  ```
+  """
+  @spec encode_animation(
+          [ImageRs.t()],
+          [non_neg_integer() | {non_neg_integer(), pos_integer()}],
+          :gif,
+          Keyword.t()
+        ) ::
+          {:ok, binary()} | {:error, String.t() | error()}
+  def encode_animation(images, delays, format \\ :gif, options \\ [])
+      when is_list(images) and is_list(delays) do
+    with {:ok, delays} <- validate_animation_delays(images, delays),
+         {:ok, checked_options} <- validate_animation_format_and_options(format, options) do
+      ImageRs.Nif.encode_animation(images, delays, format, checked_options)
+    end
+  end
+
+  @doc """
+  Encode a list of images as the pages of one file.
+
+  - **images**. Pages of the file, in order. Each page keeps its own size and color type.
+  - **format**. Either `:tiff` or `:ico`. Each image of an `:ico` file is stored
+    as PNG and must be at most 256 pixels wide and high.
+
+  ## Options
+  - `:tiff`
+    - `:compression`. One of `:none` (default), `:lzw`, `:deflate` or `:packbits`.
+  """
+  @spec encode_pages([ImageRs.t()], :tiff | :ico, Keyword.t()) ::
+          {:ok, binary()} | {:error, String.t()}
+  def encode_pages(images, format \\ :tiff, options \\ []) when is_list(images) do
+    cond do
+      images == [] ->
+        {:error, "`images` must not be empty"}
+
+      format not in [:tiff, :ico] ->
+        {:error,
+         "multi-page output format `#{inspect(format)}` is not supported, expected `:tiff` or `:ico`"}
+
+      true ->
+        with {:ok, checked_options} <- validate_output_format_and_options(format, options) do
+          ImageRs.Nif.encode_pages(images, format, checked_options)
+        end
+    end
+  end
+
+  @doc """
+  Encode an image as an `.ico` file with one entry per size, e.g. for favicons.
+
+  - **sizes**. Width and height of each entry, in `1..256`. Defaults to `[16, 32, 48, 256]`.
+  - **filter_type**. Filter used to resize the image, see `resize/4`.
+
+  Each entry is made with `resize_to_fill/4`, so non-square images are cropped
+  to a centered square rather than stretched.
+
+  ## Example
+  ```elixir
+  ico = ImageRs.encode_ico(logo, [16, 32, 48])
+  File.write!("favicon.ico", ico)
+  ```
+  """
+  @spec encode_ico(
+          ImageRs.t(),
+          [1..256],
+          :nearest | :triangle | :catmull_rom | :gaussian | :lanczos3
+        ) :: {:ok, binary()} | {:error, String.t()}
+  def encode_ico(image, sizes \\ [16, 32, 48, 256], filter_type \\ :lanczos3) do
+    if sizes != [] and Enum.all?(sizes, &(&1 in 1..256)) do
+      images = Enum.map(sizes, &resize_to_fill(image, &1, &1, filter_type))
+
+      case Enum.find(images, &match?({:error, _}, &1)) do
+        nil -> encode_pages(images, :ico)
+        error -> error
+      end
+    else
+      {:error, "`sizes` must be a non-empty list of integers in [1, 256]"}
+    end
+  end
+
   @doc """
   Saves the buffer to a file at the path specified.
+
+  Failures are returned as `{:error, error}`, see `t:error/0`, with reasons such
+  as `:enoent`, `:eacces`, `:unsupported_color_type` or `:encoding_error`.
   """
-  @spec save(ImageRs.t(), Path.t()) :: :ok | {:error, String.t()}
+  @spec save(ImageRs.t(), Path.t()) :: :ok | {:error, error()}
   def save(image, path) do
     ImageRs.Nif.save(image, path)
   end
 
   @doc """
   Saves the buffer to a file at the path specified in the specified format.
+
+  Errors are returned the same way as by `save/2`.
   """
-  @spec save_with_format(ImageRs.t(), Path.t(), output_format()) :: :ok | {:error, String.t()}
+  @spec save_with_format(ImageRs.t(), Path.t(), output_format()) ::
+          :ok | {:error, String.t() | error()}
   def save_with_format(image, path, format) do
     supported_formats = supported_formats()
 
@@ -382,38 +815,202 @@ defmodule ImageRs do
     end
   end
 
-  defp validate_output_format_and_options(:jpeg, options) do
-    q = options[:quality]
+  @doc """
+  Saves the buffer to a file at the path specified, encoded as `format` with
+  the same options as `encode_as/3`.
 
-    case q do
-      nil ->
-        {:error, "`:quality` parameter for `:jpeg` output format must be an integer in [0, 100]"}
+  The image is written to a temporary file next to `path` first, which is then
+  renamed to `path`, so `path` is either left untouched or holds the whole image.
+  Errors are returned the same way as by `encode_as/3`.
 
-      q when is_integer(q) ->
-        if 0 <= q and q <= 100 do
-          {:ok, %{"quality" => "#{q}"}}
-        else
-          {:error,
-           "`:quality` parameter for `:jpeg` output format must be an integer in [0, 100]"}
-        end
+  ## Example
+  ```elixir
+  :ok = ImageRs.save_with_options(image, "/path/to/image.jpg", :jpeg, quality: 80)
+  ```
+  """
+  @spec save_with_options(ImageRs.t(), Path.t(), output_format(), Keyword.t()) ::
+          :ok | {:error, String.t() | error()}
+  def save_with_options(image, path, format, options \\ []) do
+    with {:ok, checked_options} <- validate_output_format_and_options(format, options),
+         {:ok, checked_options} <- validate_background(checked_options, options[:background]) do
+      ImageRs.Nif.save_with_options(image, path, format, checked_options)
+    end
+  end
 
-      q when is_binary(q) ->
-        case Integer.parse(q, 10) do
-          {q, ""} ->
-            if 0 <= q and q <= 100 do
-              {:ok, %{"quality" => "#{q}"}}
-            else
-              {:error,
-               "`:quality` parameter for `:jpeg` output format must be an integer in [0, 100]"}
-            end
+  defp error_message({reason, %{message: message}}) when is_atom(reason), do: message
+  defp error_message(message), do: message
+
+  defp validate_input_format(nil), do: {:ok, nil}
 
-          :error ->
-            {:error,
-             "`:quality` parameter for `:jpeg` output format must be an integer in [0, 100]"}
+  defp validate_input_format(format) do
+    input_formats = input_formats()
+
+    if format in input_formats do
+      {:ok, format}
+    else
+      {:error, "`:format` parameter must be one of #{inspect(input_formats)}"}
+    end
+  end
+
+  defp validate_decode_options(options) do
+    checked =
+      case options[:auto_orient] do
+        nil -> {:ok, %{}}
+        auto_orient when is_boolean(auto_orient) -> {:ok, %{"auto_orient" => "#{auto_orient}"}}
+        _ -> {:error, "`:auto_orient` parameter must be a boolean"}
+      end
+
+    Enum.reduce_while([:max_width, :max_height, :max_alloc], checked, fn
+      _key, {:error, _} = error ->
+        {:halt, error}
+
+      key, {:ok, acc} ->
+        case options[key] do
+          nil ->
+            {:cont, {:ok, acc}}
+
+          value when is_integer(value) and value >= 0 ->
+            {:cont, {:ok, Map.put(acc, to_string(key), "#{value}")}}
+
+          _ ->
+            {:halt, {:error, "`#{inspect(key)}` parameter must be a non-negative integer"}}
         end
+    end)
+  end
+
+  defp validate_output_format_and_options(:png, options) do
+    compression = options[:compression]
+    filter = options[:filter]
+
+    cond do
+      compression not in [nil, :fast, :default, :best] ->
+        {:error,
+         "`:compression` parameter for `:png` output format must be one of `:fast`, `:default` or `:best`"}
+
+      filter not in [nil, :none, :sub, :up, :avg, :paeth, :adaptive] ->
+        {:error,
+         "`:filter` parameter for `:png` output format must be one of `:none`, `:sub`, `:up`, `:avg`, `:paeth` or `:adaptive`"}
+
+      true ->
+        checked_options =
+          [compression: compression, filter: filter]
+          |> Enum.reject(fn {_key, value} -> is_nil(value) end)
+          |> Map.new(fn {key, value} -> {to_string(key), to_string(value)} end)
+
+        {:ok, checked_options}
+    end
+  end
+
+  defp validate_output_format_and_options(:webp, options) do
+    lossless = Keyword.get(options, :lossless, true)
+    quality = options[:quality]
+
+    cond do
+      not is_boolean(lossless) ->
+        {:error, "`:lossless` parameter for `:webp` output format must be a boolean"}
+
+      not (is_nil(quality) or (is_number(quality) and quality >= 0 and quality <= 100)) ->
+        {:error, "`:quality` parameter for `:webp` output format must be a number in [0, 100]"}
+
+      lossless ->
+        {:ok, %{}}
+
+      is_nil(quality) ->
+        {:ok, %{"lossless" => "false"}}
+
+      true ->
+        {:ok, %{"lossless" => "false", "quality" => "#{quality}"}}
+    end
+  end
+
+  defp validate_output_format_and_options(:avif, options) do
+    speed = options[:speed]
+    quality = options[:quality]
+    threads = options[:threads]
+
+    cond do
+      not (is_nil(speed) or speed in 1..10) ->
+        {:error, "`:speed` parameter for `:avif` output format must be an integer in [1, 10]"}
+
+      not (is_nil(quality) or quality in 1..100) ->
+        {:error, "`:quality` parameter for `:avif` output format must be an integer in [1, 100]"}
+
+      not (is_nil(threads) or (is_integer(threads) and threads > 0)) ->
+        {:error, "`:threads` parameter for `:avif` output format must be a positive integer"}
+
+      true ->
+        checked_options =
+          [speed: speed, quality: quality, threads: threads]
+          |> Enum.reject(fn {_key, value} -> is_nil(value) end)
+          |> Map.new(fn {key, value} -> {to_string(key), "#{value}"} end)
+
+        {:ok, checked_options}
+    end
+  end
+
+  defp validate_output_format_and_options(:tiff, options) do
+    case options[:compression] do
+      nil ->
+        {:ok, %{}}
+
+      compression when compression in [:none, :lzw, :deflate, :packbits] ->
+        {:ok, %{"compression" => to_string(compression)}}
 
       _ ->
+        {:error,
+         "`:compression` parameter for `:tiff` output format must be one of `:none`, `:lzw`, `:deflate` or `:packbits`"}
+    end
+  end
+
+  defp validate_output_format_and_options(:jpeg, options) do
+    quality =
+      case options[:quality] do
+        q when is_binary(q) ->
+          case Integer.parse(q, 10) do
+            {parsed, ""} -> parsed
+            _ -> q
+          end
+
+        q ->
+          q
+      end
+
+    subsampling = options[:subsampling]
+    progressive = options[:progressive]
+    restart_interval = options[:restart_interval]
+    optimize_huffman = options[:optimize_huffman]
+
+    cond do
+      not (is_nil(quality) or quality in 0..100) ->
         {:error, "`:quality` parameter for `:jpeg` output format must be an integer in [0, 100]"}
+
+      subsampling not in [nil, "4:4:4", "4:2:2", "4:2:0"] ->
+        {:error,
+         "`:subsampling` parameter for `:jpeg` output format must be one of \"4:4:4\", \"4:2:2\" or \"4:2:0\""}
+
+      not (is_nil(progressive) or is_boolean(progressive)) ->
+        {:error, "`:progressive` parameter for `:jpeg` output format must be a boolean"}
+
+      not (is_nil(restart_interval) or restart_interval in 1..65535) ->
+        {:error,
+         "`:restart_interval` parameter for `:jpeg` output format must be an integer in [1, 65535]"}
+
+      not (is_nil(optimize_huffman) or is_boolean(optimize_huffman)) ->
+        {:error, "`:optimize_huffman` parameter for `:jpeg` output format must be a boolean"}
+
+      true ->
+        checked_options =
+          [
+            quality: quality,
+            subsampling: subsampling,
+            progressive: progressive,
+            restart_interval: restart_interval,
+            optimize_huffman: optimize_huffman
+          ]
+          |> Enum.reject(fn {_key, value} -> is_nil(value) end)
+          |> Map.new(fn {key, value} -> {to_string(key), "#{value}"} end)
+
+        {:ok, checked_options}
     end
   end
 
@@ -449,6 +1046,95 @@ defmodule ImageRs do
     end
   end
 
+  defp validate_background(checked_options, nil), do: {:ok, checked_options}
+
+  defp validate_background(checked_options, {r, g, b})
+       when r in 0..255 and g in 0..255 and b in 0..255 do
+    {:ok, Map.put(checked_options, "background", "#{r},#{g},#{b}")}
+  end
+
+  defp validate_background(_checked_options, _background) do
+    {:error, "`:background` must be an `{r, g, b}` tuple of integers in [0, 255]"}
+  end
+
+  defp validate_animation_delays(images, delays) do
+    if Enum.count(images) > 0 and Enum.count(images) == Enum.count(delays) do
+      delays =
+        Enum.map(delays, fn
+          ms when is_integer(ms) and ms >= 0 ->
+            {ms, 1}
+
+          {numer, denom}
+          when is_integer(numer) and numer >= 0 and is_integer(denom) and denom > 0 ->
+            {numer, denom}
+
+          _ ->
+            nil
+        end)
+
+      if Enum.all?(delays) do
+        {:ok, delays}
+      else
+        {:error,
+         "`delays` must be non-negative integers or `{numerator, denominator}` tuples in milliseconds"}
+      end
+    else
+      {:error, "`images` must not be empty and `delays` must have one entry per image"}
+    end
+  end
+
+  defp validate_animation_format_and_options(:gif, options) do
+    repeat =
+      case options[:repeat] || :infinite do
+        :infinite ->
+          {:ok, "infinite"}
+
+        n when is_integer(n) and 0 <= n and n <= 65535 ->
+          {:ok, "#{n}"}
+
+        _ ->
+          {:error, "`:repeat` parameter must be `:infinite` or an integer in [0, 65535]"}
+      end
+
+    speed =
+      case options[:speed] || 1 do
+        s when is_integer(s) and 1 <= s and s <= 30 ->
+          {:ok, "#{s}"}
+
+        _ ->
+          {:error, "`:speed` parameter for `:gif` output format must be an integer in [1, 30]"}
+      end
+
+    with {:ok, repeat} <- repeat,
+         {:ok, speed} <- speed do
+      {:ok, %{"repeat" => repeat, "speed" => speed}}
+    end
+  end
+
+  defp validate_animation_format_and_options(format, _options) do
+    {:error, "animation output format `#{inspect(format)}` is not supported, expected `:gif`"}
+  end
+
+  defp input_formats do
+    [
+      :png,
+      :jpeg,
+      :gif,
+      :webp,
+      :pnm,
+      :tiff,
+      :tga,
+      :dds,
+      :bmp,
+      :ico,
+      :hdr,
+      :exr,
+      :farbfeld,
+      :avif,
+      :qoi
+    ]
+  end
+
   defp supported_formats do
     [
       :png,
@@ -463,7 +1149,8 @@ defmodule ImageRs do
       :tiff,
       :avif,
       :qoi,
-      :webp
+      :webp,
+      :hdr
     ]
   end
 
@@ -0,0 +1,29 @@
+defmodule ImageRs.Frame do
+  @moduledoc """
+  A single frame of an animated image.
+
+  - **image**. The fully composited frame as an `ImageRs` image.
+  - **delay**. How long the frame is shown, as a `{numerator, denominator}`
+    fraction of milliseconds.
+  - **left**, **top**. Offset of the frame within the canvas as stored in the file.
+  - **disposal**. What happens to the frame's area before the next frame is drawn,
+    one of `:none`, `:background` or `:previous`. `nil` when the container
+    does not report it.
+  """
+
+  defstruct [
+    :image,
+    delay: {0, 1},
+    left: 0,
+    top: 0,
+    disposal: nil
+  ]
+
+  @type t :: %__MODULE__{
+          image: ImageRs.t(),
+          delay: {non_neg_integer(), pos_integer()},
+          left: non_neg_integer(),
+          top: non_neg_integer(),
+          disposal: :none | :background | :previous | nil
+        }
+end
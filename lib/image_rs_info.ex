@@ -0,0 +1,28 @@
+defmodule ImageRs.Info do
+  @moduledoc """
+  Image properties read from the header of an encoded image.
+
+  - **format**. The detected container format, or `nil` if it is not recognised.
+  - **width**, **height**. Dimensions of the image.
+  - **color_type**, **dtype**. The color type and data type the image decodes to.
+  - **frames**. Number of frames, or pages of a multi-page TIFF, `1` for still images.
+  """
+
+  defstruct [
+    :format,
+    :width,
+    :height,
+    :color_type,
+    :dtype,
+    frames: 1
+  ]
+
+  @type t :: %__MODULE__{
+          format: ImageRs.format() | nil,
+          width: non_neg_integer(),
+          height: non_neg_integer(),
+          color_type: :l | :la | :rgb | :rgba,
+          dtype: :u8 | :u16 | :f32,
+          frames: non_neg_integer()
+        }
+end
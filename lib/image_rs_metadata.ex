@@ -0,0 +1,38 @@
+defmodule ImageRs.Metadata do
+  @moduledoc """
+  EXIF metadata read from an encoded image.
+
+  - **exif**. The raw EXIF blob in TIFF layout, or `nil` if the image has none.
+    TIFF images store these tags in the file itself, so only the parsed tags are available.
+  - **orientation**. The EXIF orientation, `1..8`. Values outside that range are `nil`.
+  - **make**, **model**. Camera manufacturer and model.
+  - **datetime**. Capture time as stored in EXIF, e.g. `"2024:01:02 03:04:05"`.
+  - **latitude**, **longitude**. GPS position in signed decimal degrees,
+    negative for the southern and western hemispheres.
+  - **altitude**. GPS altitude in meters, negative below sea level.
+
+  Every parsed tag is `nil` if it is not present.
+  """
+
+  defstruct [
+    :exif,
+    :orientation,
+    :make,
+    :model,
+    :datetime,
+    :latitude,
+    :longitude,
+    :altitude
+  ]
+
+  @type t :: %__MODULE__{
+          exif: binary() | nil,
+          orientation: 1..8 | nil,
+          make: String.t() | nil,
+          model: String.t() | nil,
+          datetime: String.t() | nil,
+          latitude: float() | nil,
+          longitude: float() | nil,
+          altitude: float() | nil
+        }
+end
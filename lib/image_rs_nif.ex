@@ -53,10 +53,21 @@ defmodule ImageRs.Nif do
     mode: mode,
     force_build: System.get_env("IMAGE_RS_BUILD") in ["1", "true"]
 
-  def from_file(_filename), do: :erlang.nif_error(:not_loaded)
-  def from_binary(_data), do: :erlang.nif_error(:not_loaded)
+  def from_file(_filename, _format, _options), do: :erlang.nif_error(:not_loaded)
+  def from_binary(_data, _format, _options), do: :erlang.nif_error(:not_loaded)
+  def frames_from_file(_filename), do: :erlang.nif_error(:not_loaded)
+  def frames_from_binary(_data), do: :erlang.nif_error(:not_loaded)
+  def pages_from_file(_filename, _options), do: :erlang.nif_error(:not_loaded)
+  def pages_from_binary(_data, _options), do: :erlang.nif_error(:not_loaded)
+  def probe_file(_filename), do: :erlang.nif_error(:not_loaded)
+  def probe_binary(_data), do: :erlang.nif_error(:not_loaded)
+  def metadata_from_file(_filename), do: :erlang.nif_error(:not_loaded)
+  def metadata_from_binary(_data), do: :erlang.nif_error(:not_loaded)
   def new(_height, _width, _color_type, _dtype, _data), do: :erlang.nif_error(:not_loaded)
   def to_binary(_image), do: :erlang.nif_error(:not_loaded)
+  def icc_profile(_image), do: :erlang.nif_error(:not_loaded)
+  def convert_to_srgb(_image), do: :erlang.nif_error(:not_loaded)
+  def tonemap(_image, _operator, _exposure, _gamma), do: :erlang.nif_error(:not_loaded)
   def resize(_image, _height, _width, _filter_type), do: :erlang.nif_error(:not_loaded)
 
   def resize_preserve_ratio(_image, _height, _width, _filter_type),
@@ -66,6 +77,7 @@ defmodule ImageRs.Nif do
 
   def crop(_image, _x, _y, _height, _width), do: :erlang.nif_error(:not_loaded)
   def grayscale(_image), do: :erlang.nif_error(:not_loaded)
+  def convert(_image, _color_type, _dtype), do: :erlang.nif_error(:not_loaded)
   def invert(_image), do: :erlang.nif_error(:not_loaded)
   def blur(_image, _sigma), do: :erlang.nif_error(:not_loaded)
   def unsharpen(_image, _sigma, _threshold), do: :erlang.nif_error(:not_loaded)
@@ -78,7 +90,14 @@ defmodule ImageRs.Nif do
   def rotate90(_image), do: :erlang.nif_error(:not_loaded)
   def rotate180(_image), do: :erlang.nif_error(:not_loaded)
   def rotate270(_image), do: :erlang.nif_error(:not_loaded)
+  def apply_orientation(_image, _orientation), do: :erlang.nif_error(:not_loaded)
   def encode_as(_image, _format, _options), do: :erlang.nif_error(:not_loaded)
+
+  def encode_animation(_images, _delays, _format, _options),
+    do: :erlang.nif_error(:not_loaded)
+
+  def encode_pages(_images, _format, _options), do: :erlang.nif_error(:not_loaded)
   def save(_image, _path), do: :erlang.nif_error(:not_loaded)
   def save_with_format(_image, _path, _format), do: :erlang.nif_error(:not_loaded)
+  def save_with_options(_image, _path, _format, _options), do: :erlang.nif_error(:not_loaded)
 end
@@ -0,0 +1,114 @@
+use image::error::{DecodingError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{ColorType, DynamicImage, ImageBuffer, ImageError, ImageResult, Pixel};
+use moxcms::{CmsError, ColorProfile, DataColorSpace, Layout, TransformExecutor, TransformOptions};
+
+/// Transforms the pixels of `image` from the color space described by
+/// `icc_profile` into sRGB.
+///
+/// RGB profiles apply to RGB(A) images and gray profiles to L(A) images, which
+/// stay gray. CMYK profiles are rejected: the decoders hand out CMYK images
+/// already converted to RGB without the profile, and the CMYK samples cannot be
+/// recovered from that.
+pub fn convert_to_srgb(image: &DynamicImage, icc_profile: &[u8]) -> ImageResult<DynamicImage> {
+    let source = ColorProfile::new_from_slice(icc_profile)
+        .map_err(|err| ImageError::Decoding(DecodingError::new(icc_format_hint(), err)))?;
+    let target = match (source.color_space, image) {
+        (DataColorSpace::Rgb, _) if image.color().has_color() => ColorProfile::new_srgb(),
+        (DataColorSpace::Gray, _) if !image.color().has_color() => srgb_gray(),
+        (DataColorSpace::Cmyk, _) => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    icc_format_hint(),
+                    UnsupportedErrorKind::GenericFeature("CMYK profiles".to_string()),
+                ),
+            ))
+        }
+        _ => return Err(unsupported_color(image.color())),
+    };
+
+    let options = TransformOptions::default();
+    let u8_transform = |layout| source.create_transform_8bit(layout, &target, layout, options);
+    let u16_transform = |layout| source.create_transform_16bit(layout, &target, layout, options);
+    let f32_transform = |layout| source.create_transform_f32(layout, &target, layout, options);
+    Ok(match image {
+        DynamicImage::ImageLuma8(buffer) => {
+            DynamicImage::ImageLuma8(transform(buffer, u8_transform)?)
+        }
+        DynamicImage::ImageLumaA8(buffer) => {
+            DynamicImage::ImageLumaA8(transform(buffer, u8_transform)?)
+        }
+        DynamicImage::ImageRgb8(buffer) => {
+            DynamicImage::ImageRgb8(transform(buffer, u8_transform)?)
+        }
+        DynamicImage::ImageRgba8(buffer) => {
+            DynamicImage::ImageRgba8(transform(buffer, u8_transform)?)
+        }
+        DynamicImage::ImageLuma16(buffer) => {
+            DynamicImage::ImageLuma16(transform(buffer, u16_transform)?)
+        }
+        DynamicImage::ImageLumaA16(buffer) => {
+            DynamicImage::ImageLumaA16(transform(buffer, u16_transform)?)
+        }
+        DynamicImage::ImageRgb16(buffer) => {
+            DynamicImage::ImageRgb16(transform(buffer, u16_transform)?)
+        }
+        DynamicImage::ImageRgba16(buffer) => {
+            DynamicImage::ImageRgba16(transform(buffer, u16_transform)?)
+        }
+        DynamicImage::ImageRgb32F(buffer) => {
+            DynamicImage::ImageRgb32F(transform(buffer, f32_transform)?)
+        }
+        DynamicImage::ImageRgba32F(buffer) => {
+            DynamicImage::ImageRgba32F(transform(buffer, f32_transform)?)
+        }
+        _ => return Err(unsupported_color(image.color())),
+    })
+}
+
+fn transform<P: Pixel>(
+    buffer: &ImageBuffer<P, Vec<P::Subpixel>>,
+    create: impl FnOnce(
+        Layout,
+    ) -> Result<Box<dyn TransformExecutor<P::Subpixel> + Send + Sync>, CmsError>,
+) -> ImageResult<ImageBuffer<P, Vec<P::Subpixel>>>
+where
+    P::Subpixel: Default,
+{
+    let layout = match P::CHANNEL_COUNT {
+        1 => Layout::Gray,
+        2 => Layout::GrayAlpha,
+        3 => Layout::Rgb,
+        _ => Layout::Rgba,
+    };
+    let mut converted = ImageBuffer::new(buffer.width(), buffer.height());
+    create(layout)
+        .and_then(|executor| executor.transform(buffer, &mut converted))
+        .map_err(unsupported_transform)?;
+    Ok(converted)
+}
+
+/// A gray profile with the sRGB tone curve, so gray images stay gray.
+fn srgb_gray() -> ColorProfile {
+    let srgb = ColorProfile::new_srgb();
+    let mut gray = ColorProfile::new_gray_with_gamma(2.2);
+    gray.gray_trc = srgb.red_trc;
+    gray
+}
+
+fn unsupported_color(color: ColorType) -> ImageError {
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+        icc_format_hint(),
+        UnsupportedErrorKind::Color(color.into()),
+    ))
+}
+
+fn unsupported_transform(err: CmsError) -> ImageError {
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+        icc_format_hint(),
+        UnsupportedErrorKind::GenericFeature(err.to_string()),
+    ))
+}
+
+fn icc_format_hint() -> ImageFormatHint {
+    ImageFormatHint::Name("ICC".to_string())
+}
@@ -1,10 +1,15 @@
 use image::{imageops::FilterType, DynamicImage, ImageFormat};
-use rustler::{NifStruct, NifTaggedEnum, ResourceArc};
+use rustler::{Binary, NifMap, NifStruct, NifTaggedEnum, Resource, ResourceArc};
 use std::ops::Deref;
 
-use crate::get_image_detail;
+use crate::{get_image_detail, with_known_color_type};
 
-pub struct ImageRsDynamicImageRef(pub DynamicImage);
+pub struct ImageRsDynamicImageRef {
+    pub image: DynamicImage,
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+impl Resource for ImageRsDynamicImageRef {}
 
 #[derive(NifTaggedEnum)]
 pub enum ImageRsColorType {
@@ -41,6 +46,18 @@ pub enum ImageRsFilterType {
     Lanczos3,
 }
 
+#[derive(NifTaggedEnum, Clone, Copy)]
+pub enum ImageRsTonemapOperator {
+    /// `x / (1 + x)`
+    Reinhard,
+
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve
+    AcesFilmic,
+
+    /// Exposure and gamma only, clipping highlights
+    Exposure,
+}
+
 #[derive(NifTaggedEnum)]
 pub enum ImageRsOutputFormat {
     Png,
@@ -56,6 +73,26 @@ pub enum ImageRsOutputFormat {
     Avif,
     Qoi,
     Webp,
+    Hdr,
+}
+
+#[derive(NifTaggedEnum, Clone, Copy)]
+pub enum ImageRsFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Webp,
+    Pnm,
+    Tiff,
+    Tga,
+    Dds,
+    Bmp,
+    Ico,
+    Hdr,
+    Exr,
+    Farbfeld,
+    Avif,
+    Qoi,
 }
 
 #[derive(NifStruct)]
@@ -68,30 +105,109 @@ pub struct ImageRsDynamicImage {
     pub channels: u32,
     pub dtype: ImageRsDataType,
     pub shape: Vec<u32>,
+    pub format: Option<ImageRsFormat>,
+}
+
+#[derive(NifTaggedEnum)]
+pub enum ImageRsDisposalMethod {
+    /// Leave the frame on the canvas
+    None,
+
+    /// Clear the frame's area to the background
+    Background,
+
+    /// Restore the frame's area to its previous content
+    Previous,
+}
+
+#[derive(NifStruct)]
+#[module = "ImageRs.Frame"]
+pub struct ImageRsFrame {
+    pub image: ImageRsDynamicImage,
+    pub delay: (u32, u32),
+    pub left: u32,
+    pub top: u32,
+    pub disposal: Option<ImageRsDisposalMethod>,
+}
+
+#[derive(NifStruct)]
+#[module = "ImageRs.Info"]
+pub struct ImageRsInfo {
+    pub format: Option<ImageRsFormat>,
+    pub width: u32,
+    pub height: u32,
+    pub color_type: ImageRsColorType,
+    pub dtype: ImageRsDataType,
+    pub frames: u32,
+}
+
+#[derive(NifStruct)]
+#[module = "ImageRs.Metadata"]
+pub struct ImageRsMetadata<'a> {
+    pub exif: Option<Binary<'a>>,
+    pub orientation: Option<u32>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub datetime: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+}
+
+/// The second element of every `{reason, error}` term returned by the NIFs.
+#[derive(NifMap)]
+pub struct ImageRsError {
+    pub format: Option<ImageRsFormat>,
+    pub message: String,
 }
 
 impl ImageRsDynamicImageRef {
-    pub fn new(image: DynamicImage) -> Self {
-        Self(image)
+    pub fn new(image: DynamicImage, icc_profile: Option<Vec<u8>>) -> Self {
+        Self { image, icc_profile }
     }
 }
 
 impl ImageRsDynamicImage {
     pub fn new(image: DynamicImage) -> Self {
+        Self::new_with_icc_profile(image, None)
+    }
+
+    pub fn new_with_icc_profile(image: DynamicImage, icc_profile: Option<Vec<u8>>) -> Self {
+        let image = with_known_color_type(image);
         let ((height, width, channels), color_type, datatype) = get_image_detail(&image);
         Self {
-            resource: ResourceArc::new(ImageRsDynamicImageRef::new(image)),
-            width: width,
-            height: height,
-            color_type: color_type,
-            channels: channels,
+            resource: ResourceArc::new(ImageRsDynamicImageRef::new(image, icc_profile)),
+            width,
+            height,
+            color_type,
+            channels,
             dtype: datatype,
             shape: [height, width, channels].to_vec(),
+            format: None,
         }
     }
 
+    pub fn with_format(mut self, format: Option<ImageRsFormat>) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Wraps an image produced from this one, keeping its source format and,
+    /// while the image stays gray or color alike, its ICC profile.
+    pub fn derive(&self, image: DynamicImage) -> Self {
+        let icc_profile = self
+            .icc_profile()
+            .filter(|_| image.color().has_color() == self.color().has_color())
+            .map(<[u8]>::to_vec);
+        Self::new_with_icc_profile(image, icc_profile).with_format(self.format)
+    }
+
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        self.resource.icc_profile.as_deref()
+    }
+
     pub fn clone_inner(&self) -> DynamicImage {
-        self.resource.0.clone()
+        self.resource.image.clone()
     }
 }
 
@@ -99,13 +215,13 @@ impl Deref for ImageRsDynamicImage {
     type Target = DynamicImage;
 
     fn deref(&self) -> &Self::Target {
-        &self.resource.0
+        &self.resource.image
     }
 }
 
-impl Into<FilterType> for ImageRsFilterType {
-    fn into(self) -> FilterType {
-        match self {
+impl From<ImageRsFilterType> for FilterType {
+    fn from(filter: ImageRsFilterType) -> Self {
+        match filter {
             ImageRsFilterType::Nearest => FilterType::Nearest,
             ImageRsFilterType::Triangle => FilterType::Triangle,
             ImageRsFilterType::CatmullRom => FilterType::CatmullRom,
@@ -115,9 +231,9 @@ impl Into<FilterType> for ImageRsFilterType {
     }
 }
 
-impl Into<ImageFormat> for ImageRsOutputFormat {
-    fn into(self) -> ImageFormat {
-        match self {
+impl From<ImageRsOutputFormat> for ImageFormat {
+    fn from(format: ImageRsOutputFormat) -> Self {
+        match format {
             ImageRsOutputFormat::Png => ImageFormat::Png,
             ImageRsOutputFormat::Jpeg => ImageFormat::Jpeg,
             ImageRsOutputFormat::Pnm => ImageFormat::Pnm,
@@ -131,6 +247,52 @@ impl Into<ImageFormat> for ImageRsOutputFormat {
             ImageRsOutputFormat::Qoi => ImageFormat::Qoi,
             ImageRsOutputFormat::Webp => ImageFormat::WebP,
             ImageRsOutputFormat::Gif => ImageFormat::Gif,
+            ImageRsOutputFormat::Hdr => ImageFormat::Hdr,
+        }
+    }
+}
+
+impl From<ImageRsFormat> for ImageFormat {
+    fn from(format: ImageRsFormat) -> Self {
+        match format {
+            ImageRsFormat::Png => ImageFormat::Png,
+            ImageRsFormat::Jpeg => ImageFormat::Jpeg,
+            ImageRsFormat::Gif => ImageFormat::Gif,
+            ImageRsFormat::Webp => ImageFormat::WebP,
+            ImageRsFormat::Pnm => ImageFormat::Pnm,
+            ImageRsFormat::Tiff => ImageFormat::Tiff,
+            ImageRsFormat::Tga => ImageFormat::Tga,
+            ImageRsFormat::Dds => ImageFormat::Dds,
+            ImageRsFormat::Bmp => ImageFormat::Bmp,
+            ImageRsFormat::Ico => ImageFormat::Ico,
+            ImageRsFormat::Hdr => ImageFormat::Hdr,
+            ImageRsFormat::Exr => ImageFormat::OpenExr,
+            ImageRsFormat::Farbfeld => ImageFormat::Farbfeld,
+            ImageRsFormat::Avif => ImageFormat::Avif,
+            ImageRsFormat::Qoi => ImageFormat::Qoi,
+        }
+    }
+}
+
+impl ImageRsFormat {
+    pub fn from_image_format(format: ImageFormat) -> Option<Self> {
+        match format {
+            ImageFormat::Png => Some(ImageRsFormat::Png),
+            ImageFormat::Jpeg => Some(ImageRsFormat::Jpeg),
+            ImageFormat::Gif => Some(ImageRsFormat::Gif),
+            ImageFormat::WebP => Some(ImageRsFormat::Webp),
+            ImageFormat::Pnm => Some(ImageRsFormat::Pnm),
+            ImageFormat::Tiff => Some(ImageRsFormat::Tiff),
+            ImageFormat::Tga => Some(ImageRsFormat::Tga),
+            ImageFormat::Dds => Some(ImageRsFormat::Dds),
+            ImageFormat::Bmp => Some(ImageRsFormat::Bmp),
+            ImageFormat::Ico => Some(ImageRsFormat::Ico),
+            ImageFormat::Hdr => Some(ImageRsFormat::Hdr),
+            ImageFormat::OpenExr => Some(ImageRsFormat::Exr),
+            ImageFormat::Farbfeld => Some(ImageRsFormat::Farbfeld),
+            ImageFormat::Avif => Some(ImageRsFormat::Avif),
+            ImageFormat::Qoi => Some(ImageRsFormat::Qoi),
+            _ => None,
         }
     }
 }
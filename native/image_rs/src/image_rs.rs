@@ -1,11 +1,41 @@
+#[cfg(feature = "cms")]
+use crate::cms;
+#[cfg(feature = "jpeg-encoder")]
+use crate::jpeg_codec;
+use crate::metadata::parse_exif;
+#[cfg(feature = "tiff")]
+use crate::tiff_codec;
+use crate::tonemap;
+#[cfg(feature = "webp-lossy")]
+use crate::webp_lossy;
+#[cfg(any(feature = "gif", feature = "png", feature = "webp"))]
+use crate::ImageRsDisposalMethod;
 use crate::{
-    ImageRsColorType, ImageRsDataType, ImageRsDynamicImage, ImageRsFilterType, ImageRsOutputFormat,
+    ImageRsColorType, ImageRsDataType, ImageRsDynamicImage, ImageRsError, ImageRsFilterType,
+    ImageRsFormat, ImageRsFrame, ImageRsInfo, ImageRsMetadata, ImageRsOutputFormat,
+    ImageRsTonemapOperator,
+};
+#[cfg(any(feature = "gif", feature = "png", feature = "webp"))]
+use image::error::DecodingError;
+use image::error::ImageFormatHint;
+use image::metadata::Orientation;
+#[cfg(any(feature = "gif", feature = "png", feature = "webp"))]
+use image::AnimationDecoder;
+use image::{
+    ColorType, Delay, DynamicImage, Frame, ImageBuffer, ImageDecoder, ImageError, ImageFormat,
+    ImageReader, ImageResult, Limits, Luma, LumaA, Rgb, Rgba,
 };
-use image::{ColorType, DynamicImage, ImageBuffer, ImageError};
 use rustler::{Atom, Binary, Env, Error, NewBinary};
 use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, OpenOptions};
 use std::io::ErrorKind as IoErrorKind;
-use std::io::{BufWriter, Cursor, Seek, Write};
+#[cfg(feature = "gif")]
+use std::io::Read;
+use std::io::{BufRead, BufReader, BufWriter, Cursor, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::vec::Vec;
 mod atoms {
     rustler::atoms! {
@@ -29,6 +59,7 @@ mod atoms {
         unsupported_color_type,
         unsupported_format,
         bad_argument,
+        invalid_frame,
     }
 }
 
@@ -62,22 +93,486 @@ fn io_error_to_term(err: &ImageError) -> Atom {
     }
 }
 
+/// `{reason, %{format: format, message: message}}`, with the reason from
+/// `io_error_to_term` and `err`'s message.
+fn image_error_to_term(err: &ImageError) -> Error {
+    let format_hint = match err {
+        ImageError::Decoding(err) => err.format_hint(),
+        ImageError::Encoding(err) => err.format_hint(),
+        ImageError::Unsupported(err) => err.format_hint(),
+        _ => ImageFormatHint::Unknown,
+    };
+    let format = match format_hint {
+        ImageFormatHint::Exact(format) => ImageRsFormat::from_image_format(format),
+        ImageFormatHint::PathExtension(extension) => {
+            ImageFormat::from_extension(extension).and_then(ImageRsFormat::from_image_format)
+        }
+        _ => None,
+    };
+    let error = ImageRsError {
+        format,
+        message: err.to_string(),
+    };
+    Error::Term(Box::new((io_error_to_term(err), error)))
+}
+
+/// An error term shaped like `image_error_to_term`'s, for errors that do not
+/// come from `image`.
+fn error_term(reason: Atom, message: impl Into<String>) -> Error {
+    let error = ImageRsError {
+        format: None,
+        message: message.into(),
+    };
+    Error::Term(Box::new((reason, error)))
+}
+
+fn binary_copy_error() -> Error {
+    error_term(atoms::io(), "failed to copy the image into a binary")
+}
+
+fn bad_option(options: &HashMap<String, String>, key: &str) -> Error {
+    let value = options.get(key).map_or("", |value| &value[..]);
+    error_term(
+        atoms::bad_argument(),
+        format!("invalid value {value:?} for option `{key}`"),
+    )
+}
+
 #[rustler::nif(schedule = "DirtyIo")]
-pub fn from_file(filename: &str) -> Result<ImageRsDynamicImage, Error> {
-    match image::open(filename) {
-        Ok(image) => Ok(ImageRsDynamicImage::new(image)),
-        Err(ref e) => Err(Error::Term(Box::new(io_error_to_term(e)))),
+pub fn from_file(
+    filename: &str,
+    format: Option<ImageRsFormat>,
+    options: HashMap<String, String>,
+) -> Result<ImageRsDynamicImage, Error> {
+    let limits = decoding_limits(&options)?;
+    let auto_orient = options
+        .get("auto_orient")
+        .is_some_and(|value| value == "true");
+    let reader = ImageReader::open(filename).map(|mut reader| {
+        if let Some(format) = format {
+            reader.set_format(format.into());
+        }
+        reader
+    });
+    match reader
+        .map_err(ImageError::IoError)
+        .and_then(|reader| decode(reader, limits, auto_orient))
+    {
+        Ok(image) => Ok(image),
+        Err(ref e) => Err(image_error_to_term(e)),
     }
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-fn from_binary(buffer: Binary) -> Result<ImageRsDynamicImage, Error> {
-    match image::load_from_memory(buffer.as_slice()) {
-        Ok(image) => Ok(ImageRsDynamicImage::new(image)),
-        Err(ref e) => Err(Error::Term(Box::new(io_error_to_term(e)))),
+fn from_binary(
+    buffer: Binary,
+    format: Option<ImageRsFormat>,
+    options: HashMap<String, String>,
+) -> Result<ImageRsDynamicImage, Error> {
+    let limits = decoding_limits(&options)?;
+    let auto_orient = options
+        .get("auto_orient")
+        .is_some_and(|value| value == "true");
+    let cursor = Cursor::new(buffer.as_slice());
+    let reader = match format {
+        Some(format) => Ok(ImageReader::with_format(cursor, format.into())),
+        None => ImageReader::new(cursor).with_guessed_format(),
+    };
+    match reader
+        .map_err(ImageError::IoError)
+        .and_then(|reader| decode(reader, limits, auto_orient))
+    {
+        Ok(image) => Ok(image),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+fn decoding_limits(options: &HashMap<String, String>) -> Result<Limits, Error> {
+    let mut limits = Limits::default();
+    if let Some(max_width) = options.get("max_width") {
+        match max_width.parse::<u32>() {
+            Ok(max_width) => limits.max_image_width = Some(max_width),
+            Err(_) => return Err(bad_option(options, "max_width")),
+        }
+    }
+    if let Some(max_height) = options.get("max_height") {
+        match max_height.parse::<u32>() {
+            Ok(max_height) => limits.max_image_height = Some(max_height),
+            Err(_) => return Err(bad_option(options, "max_height")),
+        }
+    }
+    if let Some(max_alloc) = options.get("max_alloc") {
+        match max_alloc.parse::<u64>() {
+            Ok(max_alloc) => limits.max_alloc = Some(max_alloc),
+            Err(_) => return Err(bad_option(options, "max_alloc")),
+        }
+    }
+    Ok(limits)
+}
+
+fn decode<R: BufRead + Seek>(
+    reader: ImageReader<R>,
+    mut limits: Limits,
+    auto_orient: bool,
+) -> ImageResult<ImageRsDynamicImage> {
+    let format = reader.format().and_then(ImageRsFormat::from_image_format);
+    let (mut reader, tiff_icc_profile) = read_tiff_icc_profile(reader)?;
+    reader.limits(limits.clone());
+    let mut decoder = reader.into_decoder()?;
+    limits.reserve(decoder.total_bytes())?;
+
+    let orientation = if auto_orient {
+        decoder.orientation()?
+    } else {
+        Orientation::NoTransforms
+    };
+    let icc_profile = decoder.icc_profile()?.or(tiff_icc_profile);
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(ImageRsDynamicImage::new_with_icc_profile(image, icc_profile).with_format(format))
+}
+
+fn read_tiff_icc_profile<R: BufRead + Seek>(
+    reader: ImageReader<R>,
+) -> ImageResult<(ImageReader<R>, Option<Vec<u8>>)> {
+    #[cfg(feature = "tiff")]
+    if reader.format() == Some(ImageFormat::Tiff) {
+        let mut inner = reader.into_inner();
+        let icc_profile = tiff_codec::read_icc_profile(&mut inner)?;
+        let reader = ImageReader::with_format(inner, ImageFormat::Tiff);
+        return Ok((reader, icc_profile));
+    }
+    Ok((reader, None))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn frames_from_file(filename: &str) -> Result<Vec<ImageRsFrame>, Error> {
+    match std::fs::read(filename)
+        .map_err(ImageError::IoError)
+        .and_then(|bytes| decode_frames(&bytes))
+    {
+        Ok(frames) => Ok(frames),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn frames_from_binary(buffer: Binary) -> Result<Vec<ImageRsFrame>, Error> {
+    match decode_frames(buffer.as_slice()) {
+        Ok(frames) => Ok(frames),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+/// Placement of a frame as stored in the container, before compositing.
+#[cfg(any(feature = "gif", feature = "png", feature = "webp"))]
+struct FramePlacement {
+    left: u32,
+    top: u32,
+    disposal: ImageRsDisposalMethod,
+}
+
+fn decode_frames(bytes: &[u8]) -> ImageResult<Vec<ImageRsFrame>> {
+    let format = image::guess_format(bytes)?;
+    let mut frames = match format {
+        #[cfg(feature = "gif")]
+        ImageFormat::Gif => {
+            let frames = gif::GifDecoder::new(Cursor::new(bytes))?
+                .into_frames()
+                .collect_frames()?;
+            into_image_rs_frames(frames, gif_frame_placements(bytes)?)
+        }
+        #[cfg(feature = "png")]
+        ImageFormat::Png => {
+            let decoder = png::PngDecoder::new(Cursor::new(bytes))?;
+            if decoder.is_apng()? {
+                let frames = decoder.apng()?.into_frames().collect_frames()?;
+                into_image_rs_frames(frames, apng_frame_placements(Cursor::new(bytes))?)
+            } else {
+                vec![still_frame(DynamicImage::from_decoder(decoder)?)]
+            }
+        }
+        #[cfg(feature = "webp")]
+        ImageFormat::WebP => {
+            let decoder = webp::WebPDecoder::new(Cursor::new(bytes))?;
+            if decoder.has_animation() {
+                // image-webp composites frames internally and does not report
+                // per-frame placement, so `disposal` is left unset.
+                let frames = decoder.into_frames().collect_frames()?;
+                into_image_rs_frames(frames, Vec::new())
+            } else {
+                vec![still_frame(DynamicImage::from_decoder(decoder)?)]
+            }
+        }
+        _ => vec![still_frame(image::load_from_memory_with_format(
+            bytes, format,
+        )?)],
+    };
+
+    let source_format = ImageRsFormat::from_image_format(format);
+    for frame in frames.iter_mut() {
+        frame.image.format = source_format;
+    }
+    Ok(frames)
+}
+
+fn still_frame(image: DynamicImage) -> ImageRsFrame {
+    ImageRsFrame {
+        image: ImageRsDynamicImage::new(image),
+        delay: (0, 1),
+        left: 0,
+        top: 0,
+        disposal: None,
+    }
+}
+
+#[cfg(any(feature = "gif", feature = "png", feature = "webp"))]
+fn into_image_rs_frames(frames: Vec<Frame>, placements: Vec<FramePlacement>) -> Vec<ImageRsFrame> {
+    let mut placements = placements.into_iter();
+    frames
+        .into_iter()
+        .map(|frame| {
+            let delay = frame.delay().numer_denom_ms();
+            let (left, top, disposal) = match placements.next() {
+                Some(placement) => (placement.left, placement.top, Some(placement.disposal)),
+                None => (frame.left(), frame.top(), None),
+            };
+            ImageRsFrame {
+                image: ImageRsDynamicImage::new(DynamicImage::ImageRgba8(frame.into_buffer())),
+                delay,
+                left,
+                top,
+                disposal,
+            }
+        })
+        .collect()
+}
+
+#[cfg(feature = "gif")]
+fn gif_frame_placements<R: Read>(reader: R) -> ImageResult<Vec<FramePlacement>> {
+    let to_image_error = |e: ::gif::DecodingError| {
+        ImageError::Decoding(DecodingError::new(ImageFormat::Gif.into(), e))
+    };
+    // Only the frame headers are needed, so leave the image data LZW-compressed.
+    let mut options = ::gif::DecodeOptions::new();
+    options.skip_frame_decoding(true);
+    let mut decoder = options.read_info(reader).map_err(to_image_error)?;
+    let mut placements = Vec::new();
+    while let Some(frame) = decoder.next_frame_info().map_err(to_image_error)? {
+        placements.push(FramePlacement {
+            left: u32::from(frame.left),
+            top: u32::from(frame.top),
+            disposal: match frame.dispose {
+                ::gif::DisposalMethod::Background => ImageRsDisposalMethod::Background,
+                ::gif::DisposalMethod::Previous => ImageRsDisposalMethod::Previous,
+                _ => ImageRsDisposalMethod::None,
+            },
+        });
+    }
+    Ok(placements)
+}
+
+#[cfg(feature = "png")]
+fn apng_frame_placements<R: BufRead + Seek>(reader: R) -> ImageResult<Vec<FramePlacement>> {
+    let to_image_error = |e: ::png::DecodingError| {
+        ImageError::Decoding(DecodingError::new(ImageFormat::Png.into(), e))
+    };
+    let mut reader = ::png::Decoder::new(reader)
+        .read_info()
+        .map_err(to_image_error)?;
+    let num_frames = reader
+        .info()
+        .animation_control
+        .map_or(0, |control| control.num_frames);
+    let mut placements = Vec::new();
+    for i in 0..num_frames {
+        // The default image is the first frame only if an fcTL chunk precedes it.
+        let control = match reader.info().frame_control {
+            Some(control) if i == 0 => control,
+            _ => *reader.next_frame_info().map_err(to_image_error)?,
+        };
+        placements.push(FramePlacement {
+            left: control.x_offset,
+            top: control.y_offset,
+            disposal: match control.dispose_op {
+                ::png::DisposeOp::None => ImageRsDisposalMethod::None,
+                ::png::DisposeOp::Background => ImageRsDisposalMethod::Background,
+                ::png::DisposeOp::Previous => ImageRsDisposalMethod::Previous,
+            },
+        });
+    }
+    Ok(placements)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn pages_from_file(
+    filename: &str,
+    options: HashMap<String, String>,
+) -> Result<Vec<ImageRsDynamicImage>, Error> {
+    let limits = decoding_limits(&options)?;
+    match std::fs::read(filename)
+        .map_err(ImageError::IoError)
+        .and_then(|bytes| decode_pages(&bytes, limits))
+    {
+        Ok(pages) => Ok(pages),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn pages_from_binary(
+    buffer: Binary,
+    options: HashMap<String, String>,
+) -> Result<Vec<ImageRsDynamicImage>, Error> {
+    let limits = decoding_limits(&options)?;
+    match decode_pages(buffer.as_slice(), limits) {
+        Ok(pages) => Ok(pages),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+/// Decodes every page of a multi-page file, or the only image of any other file.
+fn decode_pages(bytes: &[u8], limits: Limits) -> ImageResult<Vec<ImageRsDynamicImage>> {
+    let format = image::guess_format(bytes)?;
+    match format {
+        #[cfg(feature = "tiff")]
+        ImageFormat::Tiff => {
+            let source_format = ImageRsFormat::from_image_format(format);
+            Ok(tiff_codec::decode_pages(Cursor::new(bytes), limits)?
+                .into_iter()
+                .map(|(image, icc_profile)| {
+                    ImageRsDynamicImage::new_with_icc_profile(image, icc_profile)
+                        .with_format(source_format)
+                })
+                .collect())
+        }
+        _ => {
+            let reader = ImageReader::with_format(Cursor::new(bytes), format);
+            Ok(vec![decode(reader, limits, false)?])
+        }
+    }
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn probe_file(filename: &str) -> Result<ImageRsInfo, Error> {
+    match std::fs::File::open(filename)
+        .map_err(ImageError::IoError)
+        .and_then(|file| probe(BufReader::new(file)))
+    {
+        Ok(info) => Ok(info),
+        Err(ref e) => Err(image_error_to_term(e)),
     }
 }
 
+#[rustler::nif(schedule = "DirtyCpu")]
+fn probe_binary(buffer: Binary) -> Result<ImageRsInfo, Error> {
+    match probe(Cursor::new(buffer.as_slice())) {
+        Ok(info) => Ok(info),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+/// Reads the image header without decoding any pixels.
+fn probe<R: BufRead + Seek>(mut reader: R) -> ImageResult<ImageRsInfo> {
+    let start = reader.stream_position()?;
+    let image_reader = ImageReader::new(&mut reader).with_guessed_format()?;
+    let format = image_reader.format();
+    let decoder = image_reader.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let (_channels, color_type, dtype) =
+        get_color_type_detail(known_color_type(decoder.color_type()));
+    drop(decoder);
+
+    reader.seek(SeekFrom::Start(start))?;
+    let frames = count_frames(reader, format)?;
+
+    Ok(ImageRsInfo {
+        format: format.and_then(ImageRsFormat::from_image_format),
+        width,
+        height,
+        color_type,
+        dtype,
+        frames,
+    })
+}
+
+fn count_frames<R: BufRead + Seek>(reader: R, format: Option<ImageFormat>) -> ImageResult<u32> {
+    match format {
+        #[cfg(feature = "gif")]
+        Some(ImageFormat::Gif) => Ok(gif_frame_placements(reader)?.len() as u32),
+        #[cfg(feature = "png")]
+        Some(ImageFormat::Png) => {
+            let reader = ::png::Decoder::new(reader).read_info().map_err(|e| {
+                ImageError::Decoding(DecodingError::new(ImageFormat::Png.into(), e))
+            })?;
+            Ok(reader
+                .info()
+                .animation_control
+                .map_or(1, |control| control.num_frames))
+        }
+        #[cfg(feature = "webp")]
+        Some(ImageFormat::WebP) => {
+            let decoder = image_webp::WebPDecoder::new(reader).map_err(|e| {
+                ImageError::Decoding(DecodingError::new(ImageFormat::WebP.into(), e))
+            })?;
+            Ok(decoder.num_frames().max(1))
+        }
+        #[cfg(feature = "tiff")]
+        Some(ImageFormat::Tiff) => tiff_codec::count_pages(reader),
+        _ => {
+            let _ = reader;
+            Ok(1)
+        }
+    }
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn metadata_from_file<'a>(env: Env<'a>, filename: &str) -> Result<ImageRsMetadata<'a>, Error> {
+    match std::fs::read(filename)
+        .map_err(ImageError::IoError)
+        .and_then(|bytes| read_metadata(env, &bytes))
+    {
+        Ok(metadata) => Ok(metadata),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn metadata_from_binary<'a>(env: Env<'a>, buffer: Binary) -> Result<ImageRsMetadata<'a>, Error> {
+    match read_metadata(env, buffer.as_slice()) {
+        Ok(metadata) => Ok(metadata),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+fn read_metadata<'a>(env: Env<'a>, bytes: &[u8]) -> ImageResult<ImageRsMetadata<'a>> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let format = reader.format();
+    let raw = reader.into_decoder()?.exif_metadata()?;
+    let tags = match (&raw, format) {
+        (Some(raw), _) => parse_exif(raw),
+        // TIFF files keep these tags in their own image file directories.
+        (None, Some(ImageFormat::Tiff)) => parse_exif(bytes),
+        (None, _) => Default::default(),
+    };
+
+    let exif = raw.map(|raw| {
+        let mut binary = NewBinary::new(env, raw.len());
+        binary.as_mut_slice().copy_from_slice(&raw);
+        Binary::from(binary)
+    });
+    Ok(ImageRsMetadata {
+        exif,
+        orientation: tags.orientation,
+        make: tags.make,
+        model: tags.model,
+        datetime: tags.datetime,
+        latitude: tags.latitude,
+        longitude: tags.longitude,
+        altitude: tags.altitude,
+    })
+}
+
 fn as_u16_vec(image_bytes: &[u8], width: u32, height: u32, channels: u32) -> Option<Vec<u16>> {
     if width as usize * height as usize * channels as usize * 2 != image_bytes.len() {
         return None;
@@ -118,87 +613,123 @@ fn new<'a>(
     data_type: ImageRsDataType,
     data: Binary<'a>,
 ) -> Result<ImageRsDynamicImage, Error> {
+    let invalid_image_data = || {
+        error_term(
+            atoms::invalid_image_data(),
+            "the data does not match the shape and data type",
+        )
+    };
+    let unknown_data_type = || {
+        error_term(
+            atoms::unsupported_image_data(),
+            "images cannot be created with an unknown data type",
+        )
+    };
     let image_bytes = data.as_slice();
     let image = match color_type {
         ImageRsColorType::L => match data_type {
             ImageRsDataType::U8 => ImageBuffer::from_raw(width, height, image_bytes.to_vec())
-                .map(|buf| DynamicImage::ImageLuma8(buf))
-                .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data()))),
+                .map(DynamicImage::ImageLuma8)
+                .ok_or_else(invalid_image_data),
             ImageRsDataType::U16 => {
                 if let Some(image_data) = as_u16_vec(image_bytes, width, height, 1) {
                     ImageBuffer::from_raw(width, height, image_data)
-                        .map(|buf| DynamicImage::ImageLuma16(buf))
-                        .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data())))
+                        .map(DynamicImage::ImageLuma16)
+                        .ok_or_else(invalid_image_data)
+                } else {
+                    return Err(invalid_image_data());
+                }
+            }
+            // `DynamicImage` has no gray float variants, so `From` makes L and La RGB(A).
+            ImageRsDataType::F32 => {
+                if let Some(image_data) = as_f32_vec(image_bytes, width, height, 1) {
+                    ImageBuffer::<Luma<f32>, _>::from_raw(width, height, image_data)
+                        .map(DynamicImage::from)
+                        .ok_or_else(invalid_image_data)
                 } else {
-                    return Err(Error::Term(Box::new(atoms::invalid_image_data())));
+                    return Err(invalid_image_data());
                 }
             }
-            _ => return Err(Error::Term(Box::new(atoms::unsupported_image_data()))),
+            ImageRsDataType::Unknown => return Err(unknown_data_type()),
         },
         ImageRsColorType::La => match data_type {
             ImageRsDataType::U8 => ImageBuffer::from_raw(width, height, image_bytes.to_vec())
-                .map(|buf| DynamicImage::ImageLumaA8(buf))
-                .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data()))),
+                .map(DynamicImage::ImageLumaA8)
+                .ok_or_else(invalid_image_data),
             ImageRsDataType::U16 => {
                 if let Some(image_data) = as_u16_vec(image_bytes, width, height, 2) {
                     ImageBuffer::from_raw(width, height, image_data)
-                        .map(|buf| DynamicImage::ImageLumaA16(buf))
-                        .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data())))
+                        .map(DynamicImage::ImageLumaA16)
+                        .ok_or_else(invalid_image_data)
+                } else {
+                    return Err(invalid_image_data());
+                }
+            }
+            ImageRsDataType::F32 => {
+                if let Some(image_data) = as_f32_vec(image_bytes, width, height, 2) {
+                    ImageBuffer::<LumaA<f32>, _>::from_raw(width, height, image_data)
+                        .map(DynamicImage::from)
+                        .ok_or_else(invalid_image_data)
                 } else {
-                    return Err(Error::Term(Box::new(atoms::invalid_image_data())));
+                    return Err(invalid_image_data());
                 }
             }
-            _ => return Err(Error::Term(Box::new(atoms::unsupported_image_data()))),
+            ImageRsDataType::Unknown => return Err(unknown_data_type()),
         },
         ImageRsColorType::Rgb => match data_type {
             ImageRsDataType::U8 => ImageBuffer::from_raw(width, height, image_bytes.to_vec())
-                .map(|buf| DynamicImage::ImageRgb8(buf))
-                .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data()))),
+                .map(DynamicImage::ImageRgb8)
+                .ok_or_else(invalid_image_data),
             ImageRsDataType::U16 => {
                 if let Some(image_data) = as_u16_vec(image_bytes, width, height, 3) {
                     ImageBuffer::from_raw(width, height, image_data)
-                        .map(|buf| DynamicImage::ImageRgb16(buf))
-                        .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data())))
+                        .map(DynamicImage::ImageRgb16)
+                        .ok_or_else(invalid_image_data)
                 } else {
-                    return Err(Error::Term(Box::new(atoms::invalid_image_data())));
+                    return Err(invalid_image_data());
                 }
             }
             ImageRsDataType::F32 => {
                 if let Some(image_data) = as_f32_vec(image_bytes, width, height, 3) {
                     ImageBuffer::from_raw(width, height, image_data)
-                        .map(|buf| DynamicImage::ImageRgb32F(buf))
-                        .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data())))
+                        .map(DynamicImage::ImageRgb32F)
+                        .ok_or_else(invalid_image_data)
                 } else {
-                    return Err(Error::Term(Box::new(atoms::invalid_image_data())));
+                    return Err(invalid_image_data());
                 }
             }
-            _ => return Err(Error::Term(Box::new(atoms::unsupported_image_data()))),
+            ImageRsDataType::Unknown => return Err(unknown_data_type()),
         },
         ImageRsColorType::Rgba => match data_type {
             ImageRsDataType::U8 => ImageBuffer::from_raw(width, height, image_bytes.to_vec())
-                .map(|buf| DynamicImage::ImageRgba8(buf))
-                .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data()))),
+                .map(DynamicImage::ImageRgba8)
+                .ok_or_else(invalid_image_data),
             ImageRsDataType::U16 => {
                 if let Some(image_data) = as_u16_vec(image_bytes, width, height, 4) {
                     ImageBuffer::from_raw(width, height, image_data)
-                        .map(|buf| DynamicImage::ImageRgba16(buf))
-                        .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data())))
+                        .map(DynamicImage::ImageRgba16)
+                        .ok_or_else(invalid_image_data)
                 } else {
-                    return Err(Error::Term(Box::new(atoms::invalid_image_data())));
+                    return Err(invalid_image_data());
                 }
             }
             ImageRsDataType::F32 => {
                 if let Some(image_data) = as_f32_vec(image_bytes, width, height, 4) {
                     ImageBuffer::from_raw(width, height, image_data)
-                        .map(|buf| DynamicImage::ImageRgba32F(buf))
-                        .ok_or_else(|| Error::Term(Box::new(atoms::invalid_image_data())))
+                        .map(DynamicImage::ImageRgba32F)
+                        .ok_or_else(invalid_image_data)
                 } else {
-                    return Err(Error::Term(Box::new(atoms::invalid_image_data())));
+                    return Err(invalid_image_data());
                 }
             }
-            _ => return Err(Error::Term(Box::new(atoms::unsupported_image_data()))),
+            ImageRsDataType::Unknown => return Err(unknown_data_type()),
         },
-        _ => return Err(Error::Term(Box::new(atoms::unsupported_color_type()))),
+        ImageRsColorType::Unknown => {
+            return Err(error_term(
+                atoms::unsupported_color_type(),
+                "images cannot be created with an unknown color type",
+            ))
+        }
     };
 
     match image {
@@ -213,7 +744,7 @@ fn to_binary<'a>(env: Env<'a>, image: ImageRsDynamicImage) -> Result<Binary<'a>,
     let mut binary = NewBinary::new(env, slice.len());
     match binary.as_mut_slice().write_all(slice) {
         Ok(_) => Ok(Binary::from(binary)),
-        Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+        Err(_) => Err(binary_copy_error()),
     }
 }
 
@@ -224,11 +755,7 @@ fn resize(
     width: u32,
     filter: ImageRsFilterType,
 ) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.resize_exact(
-        width,
-        height,
-        filter.into(),
-    )))
+    Ok(image.derive(image.resize_exact(width, height, filter.into())))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
@@ -238,11 +765,7 @@ fn resize_preserve_ratio(
     width: u32,
     filter: ImageRsFilterType,
 ) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.resize(
-        width,
-        height,
-        filter.into(),
-    )))
+    Ok(image.derive(image.resize(width, height, filter.into())))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
@@ -252,11 +775,7 @@ fn resize_to_fill(
     width: u32,
     filter: ImageRsFilterType,
 ) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.resize_to_fill(
-        width,
-        height,
-        filter.into(),
-    )))
+    Ok(image.derive(image.resize_to_fill(width, height, filter.into())))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
@@ -267,26 +786,59 @@ fn crop(
     height: u32,
     width: u32,
 ) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(
-        image.crop_imm(x, y, width, height),
-    ))
+    Ok(image.derive(image.crop_imm(x, y, width, height)))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn grayscale(image: ImageRsDynamicImage) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.grayscale()))
+    Ok(image.derive(image.grayscale()))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn convert(
+    image: ImageRsDynamicImage,
+    color: ImageRsColorType,
+    dtype: ImageRsDataType,
+) -> Result<ImageRsDynamicImage, Error> {
+    let (gray, alpha) = match color {
+        ImageRsColorType::L => (true, false),
+        ImageRsColorType::La => (true, true),
+        ImageRsColorType::Rgb => (false, false),
+        ImageRsColorType::Rgba => (false, true),
+        ImageRsColorType::Unknown => {
+            return Err(error_term(
+                atoms::unsupported_color_type(),
+                "images cannot be converted to an unknown color type",
+            ))
+        }
+    };
+    let depth = match dtype {
+        ImageRsDataType::U8 => 1,
+        ImageRsDataType::U16 => 2,
+        ImageRsDataType::F32 => 4,
+        ImageRsDataType::Unknown => {
+            return Err(error_term(
+                atoms::unsupported_image_data(),
+                "images cannot be converted to an unknown data type",
+            ))
+        }
+    };
+    match color_type(gray, alpha, depth) {
+        target if target == image.color() => Ok(image),
+        target => Ok(image.derive(convert_color(&image, target))),
+    }
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn invert(image: ImageRsDynamicImage) -> Result<ImageRsDynamicImage, Error> {
     let mut new_image = image.clone();
     new_image.invert();
-    Ok(ImageRsDynamicImage::new(new_image))
+    Ok(image.derive(new_image))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn blur(image: ImageRsDynamicImage, sigma: f32) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.blur(sigma)))
+    Ok(image.derive(image.blur(sigma)))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
@@ -295,52 +847,123 @@ fn unsharpen(
     sigma: f32,
     threshold: i32,
 ) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.unsharpen(sigma, threshold)))
+    Ok(image.derive(image.unsharpen(sigma, threshold)))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn filter3x3(image: ImageRsDynamicImage, kernel: Vec<f32>) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.filter3x3(&kernel)))
+    Ok(image.derive(image.filter3x3(&kernel)))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn adjust_contrast(image: ImageRsDynamicImage, c: f32) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.adjust_contrast(c)))
+    Ok(image.derive(image.adjust_contrast(c)))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn brighten(image: ImageRsDynamicImage, value: i32) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.brighten(value)))
+    Ok(image.derive(image.brighten(value)))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn huerotate(image: ImageRsDynamicImage, value: i32) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.huerotate(value)))
+    Ok(image.derive(image.huerotate(value)))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn flipv(image: ImageRsDynamicImage) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.flipv()))
+    Ok(image.derive(image.flipv()))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn fliph(image: ImageRsDynamicImage) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.fliph()))
+    Ok(image.derive(image.fliph()))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn rotate90(image: ImageRsDynamicImage) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.rotate90()))
+    Ok(image.derive(image.rotate90()))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn rotate180(image: ImageRsDynamicImage) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.rotate180()))
+    Ok(image.derive(image.rotate180()))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 fn rotate270(image: ImageRsDynamicImage) -> Result<ImageRsDynamicImage, Error> {
-    Ok(ImageRsDynamicImage::new(image.rotate270()))
+    Ok(image.derive(image.rotate270()))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn apply_orientation(
+    image: ImageRsDynamicImage,
+    orientation: u8,
+) -> Result<ImageRsDynamicImage, Error> {
+    match Orientation::from_exif(orientation) {
+        Some(orientation) => {
+            let mut new_image = image.clone();
+            new_image.apply_orientation(orientation);
+            Ok(image.derive(new_image))
+        }
+        None => Err(error_term(
+            atoms::bad_argument(),
+            format!("invalid EXIF orientation {orientation}"),
+        )),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn convert_to_srgb(image: ImageRsDynamicImage) -> Result<ImageRsDynamicImage, Error> {
+    match image.icc_profile() {
+        None => Ok(image),
+        #[cfg(feature = "cms")]
+        Some(icc_profile) => match cms::convert_to_srgb(&image, icc_profile) {
+            Ok(converted) => Ok(ImageRsDynamicImage::new(converted).with_format(image.format)),
+            Err(ref e) => Err(image_error_to_term(e)),
+        },
+        #[cfg(not(feature = "cms"))]
+        Some(_) => Err(error_term(
+            atoms::unsupported_format(),
+            "ICC profile conversion needs the `cms` feature",
+        )),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn tonemap(
+    image: ImageRsDynamicImage,
+    operator: ImageRsTonemapOperator,
+    exposure: f32,
+    gamma: f32,
+) -> Result<ImageRsDynamicImage, Error> {
+    if !(gamma > 0.0 && exposure.is_finite()) {
+        return Err(error_term(
+            atoms::bad_argument(),
+            "`gamma` must be positive and `exposure` finite",
+        ));
+    }
+    match tonemap::tonemap(&image, operator, exposure, gamma) {
+        Some(mapped) => Ok(ImageRsDynamicImage::new(mapped).with_format(image.format)),
+        None => Err(error_term(
+            atoms::unsupported_color_type(),
+            "only Rgb32F and Rgba32F images can be tonemapped",
+        )),
+    }
+}
+
+#[rustler::nif]
+fn icc_profile<'a>(env: Env<'a>, image: ImageRsDynamicImage) -> Result<Option<Binary<'a>>, Error> {
+    match image.icc_profile() {
+        Some(icc_profile) => {
+            let mut binary = NewBinary::new(env, icc_profile.len());
+            match binary.as_mut_slice().write_all(icc_profile) {
+                Ok(_) => Ok(Some(Binary::from(binary))),
+                Err(_) => Err(binary_copy_error()),
+            }
+        }
+        None => Ok(None),
+    }
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
@@ -362,10 +985,111 @@ fn encode_as<'a>(
             let mut binary = NewBinary::new(env, bytes.len());
             match binary.as_mut_slice().write_all(bytes) {
                 Ok(_) => Ok(Binary::from(binary)),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(_) => Err(binary_copy_error()),
             }
         }
-        Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+        Err(_) => Err(binary_copy_error()),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn encode_animation<'a>(
+    env: Env<'a>,
+    images: Vec<ImageRsDynamicImage>,
+    delays: Vec<(u32, u32)>,
+    format: ImageRsOutputFormat,
+    options: HashMap<String, String>,
+) -> Result<Binary<'a>, Error> {
+    if images.is_empty()
+        || images.len() != delays.len()
+        || delays.iter().any(|&(_, denom)| denom == 0)
+    {
+        return Err(error_term(
+            atoms::bad_argument(),
+            "expected one delay with a non-zero denominator per image",
+        ));
+    }
+    let (width, height) = (images[0].width(), images[0].height());
+    if let Some((index, image)) = images
+        .iter()
+        .enumerate()
+        .find(|(_, image)| (image.width(), image.height()) != (width, height))
+    {
+        return Err(error_term(
+            atoms::invalid_frame(),
+            format!(
+                "frame {} is {}x{} but the first frame is {}x{}",
+                index,
+                image.width(),
+                image.height(),
+                width,
+                height
+            ),
+        ));
+    }
+
+    let frames = images
+        .iter()
+        .zip(delays)
+        .map(|(image, (numer, denom))| {
+            Frame::from_parts(
+                image.to_rgba8(),
+                0,
+                0,
+                Delay::from_numer_denom_ms(numer, denom),
+            )
+        })
+        .collect();
+
+    let c = Cursor::new(Vec::new());
+    let mut buffer = BufWriter::new(c);
+    into_animated_output_format(&mut buffer, frames, format, &options)?;
+
+    match buffer.seek(std::io::SeekFrom::Start(0)) {
+        Ok(_) => {
+            let cursor = buffer.get_ref();
+            let bytes = cursor.get_ref();
+
+            let mut binary = NewBinary::new(env, bytes.len());
+            match binary.as_mut_slice().write_all(bytes) {
+                Ok(_) => Ok(Binary::from(binary)),
+                Err(_) => Err(binary_copy_error()),
+            }
+        }
+        Err(_) => Err(binary_copy_error()),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+fn encode_pages<'a>(
+    env: Env<'a>,
+    images: Vec<ImageRsDynamicImage>,
+    format: ImageRsOutputFormat,
+    options: HashMap<String, String>,
+) -> Result<Binary<'a>, Error> {
+    if images.is_empty() {
+        return Err(error_term(
+            atoms::bad_argument(),
+            "expected at least one image",
+        ));
+    }
+
+    let c = Cursor::new(Vec::new());
+    let mut buffer = BufWriter::new(c);
+    into_multipage_output_format(&mut buffer, &images, format, &options)?;
+
+    match buffer.seek(std::io::SeekFrom::Start(0)) {
+        Ok(_) => {
+            let cursor = buffer.get_ref();
+            let bytes = cursor.get_ref();
+
+            let mut binary = NewBinary::new(env, bytes.len());
+            match binary.as_mut_slice().write_all(bytes) {
+                Ok(_) => Ok(Binary::from(binary)),
+                Err(_) => Err(binary_copy_error()),
+            }
+        }
+        Err(_) => Err(binary_copy_error()),
     }
 }
 
@@ -373,7 +1097,7 @@ fn encode_as<'a>(
 fn save(image: ImageRsDynamicImage, path: String) -> Result<(), Error> {
     match image.save(path) {
         Ok(_) => Ok(()),
-        Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+        Err(ref e) => Err(image_error_to_term(e)),
     }
 }
 
@@ -385,14 +1109,106 @@ fn save_with_format(
 ) -> Result<(), Error> {
     match image.save_with_format(path, format.into()) {
         Ok(_) => Ok(()),
-        Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn save_with_options(
+    image: ImageRsDynamicImage,
+    path: String,
+    format: ImageRsOutputFormat,
+    options: HashMap<String, String>,
+) -> Result<(), Error> {
+    let path = Path::new(&path);
+    let temp_path = match path.file_name() {
+        Some(file_name) => path.with_file_name(temp_file_name(file_name)),
+        None => {
+            return Err(error_term(
+                atoms::bad_argument(),
+                "the path does not name a file",
+            ))
+        }
+    };
+    let file = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)
+    {
+        Ok(file) => file,
+        Err(err) => return Err(image_error_to_term(&err.into())),
+    };
+
+    let mut buffer = BufWriter::new(file);
+    let written = into_output_format(&mut buffer, &image, format, &options).and_then(|_| {
+        buffer
+            .into_inner()
+            .map_err(|err| err.into_error())
+            .and_then(|file| file.sync_all())
+            .and_then(|_| fs::rename(&temp_path, path))
+            .map_err(|err| image_error_to_term(&err.into()))
+    });
+    if written.is_err() {
+        let _ = fs::remove_file(&temp_path);
     }
+    written
+}
+
+/// A hidden name next to `file_name` that no other save, in this or another
+/// VM, writes to at the same time.
+fn temp_file_name(file_name: &OsStr) -> OsString {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut temp_file_name = OsString::from(".");
+    temp_file_name.push(file_name);
+    temp_file_name.push(format!(
+        ".{}.{}.tmp",
+        process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    temp_file_name
 }
 
 pub fn get_image_detail(
     image: &DynamicImage,
 ) -> ((u32, u32, u32), ImageRsColorType, ImageRsDataType) {
-    let (channels, color, datatype) = match image.color() {
+    let (channels, color, datatype) = get_color_type_detail(image.color());
+    let width = image.width();
+    let height = image.height();
+    ((height, width, channels), color, datatype)
+}
+
+/// `image`, converted to `known_color_type` if it has any other color type.
+pub fn with_known_color_type(image: DynamicImage) -> DynamicImage {
+    let color = known_color_type(image.color());
+    match color == image.color() {
+        true => image,
+        false => convert_color(&image, color),
+    }
+}
+
+/// The nearest color type `get_color_type_detail` has channels, a color type
+/// and a data type for; `color` itself for all but future ones.
+fn known_color_type(color: ColorType) -> ColorType {
+    match color {
+        ColorType::L8
+        | ColorType::La8
+        | ColorType::Rgb8
+        | ColorType::Rgba8
+        | ColorType::L16
+        | ColorType::La16
+        | ColorType::Rgb16
+        | ColorType::Rgba16
+        | ColorType::Rgb32F
+        | ColorType::Rgba32F => color,
+        _ => {
+            let depth = color.bytes_per_pixel() / color.channel_count().max(1);
+            color_type(!color.has_color(), color.has_alpha(), depth)
+        }
+    }
+}
+
+fn get_color_type_detail(color_type: ColorType) -> (u32, ImageRsColorType, ImageRsDataType) {
+    match color_type {
         ColorType::L8 => (1u32, ImageRsColorType::L, ImageRsDataType::U8),
         ColorType::La8 => (2u32, ImageRsColorType::La, ImageRsDataType::U8),
         ColorType::Rgb8 => (3u32, ImageRsColorType::Rgb, ImageRsDataType::U8),
@@ -404,10 +1220,7 @@ pub fn get_image_detail(
         ColorType::Rgb32F => (3u32, ImageRsColorType::Rgb, ImageRsDataType::F32),
         ColorType::Rgba32F => (4u32, ImageRsColorType::Rgba, ImageRsDataType::F32),
         _ => (0, ImageRsColorType::Unknown, ImageRsDataType::Unknown),
-    };
-    let width = image.width();
-    let height = image.height();
-    ((height, width, channels), color, datatype)
+    }
 }
 
 use image::codecs::*;
@@ -419,33 +1232,47 @@ fn into_output_format<W: std::io::Write + Seek>(
     format: ImageRsOutputFormat,
     options: &HashMap<String, String>,
 ) -> Result<(), Error> {
-    let ((height, width, _channels), _color, _dtype) = get_image_detail(&image);
+    let converted = encodable_image(image, &format, options)?;
+    let image = converted.as_ref().unwrap_or(image);
+    let ((height, width, _channels), _color, _dtype) = get_image_detail(image);
     let buf = image.as_bytes();
     let color = image.color();
     match format {
         #[cfg(feature = "png")]
         ImageRsOutputFormat::Png => {
-            match png::PngEncoder::new(buffered_write).write_image(buf, width, height, color.into()) {
+            let compression = match options.get("compression").map(|value| &value[..]) {
+                Some("fast") => png::CompressionType::Fast,
+                Some("default") => png::CompressionType::Default,
+                Some("best") => png::CompressionType::Best,
+                Some(_) => return Err(bad_option(options, "compression")),
+                None => png::CompressionType::default(),
+            };
+            let filter = match options.get("filter").map(|value| &value[..]) {
+                Some("none") => png::FilterType::NoFilter,
+                Some("sub") => png::FilterType::Sub,
+                Some("up") => png::FilterType::Up,
+                Some("avg") => png::FilterType::Avg,
+                Some("paeth") => png::FilterType::Paeth,
+                Some("adaptive") => png::FilterType::Adaptive,
+                Some(_) => return Err(bad_option(options, "filter")),
+                None => png::FilterType::default(),
+            };
+            let mut encoder =
+                png::PngEncoder::new_with_quality(buffered_write, compression, filter);
+            embed_icc_profile(&mut encoder, image)?;
+            match encoder.write_image(buf, width, height, color.into()) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "jpeg")]
         ImageRsOutputFormat::Jpeg => {
-            if let Some(quality_string) = options.get("quality") {
-                if let Ok(q) = quality_string.parse::<u8>() {
-                    match jpeg::JpegEncoder::new_with_quality(buffered_write, q)
-                        .write_image(buf, width, height, color.into())
-                    {
-                        Ok(_) => Ok(()),
-                        Err(_) => Err(Error::Term(Box::new(atoms::io()))),
-                    }
-                } else {
-                    Err(Error::Term(Box::new(atoms::bad_argument())))
-                }
-            } else {
-                Err(Error::Term(Box::new(atoms::bad_argument())))
-            }
+            let quality = match options.get("quality").map(|value| value.parse::<u8>()) {
+                None => 75,
+                Some(Ok(quality)) if quality <= 100 => quality,
+                Some(_) => return Err(bad_option(options, "quality")),
+            };
+            encode_jpeg(buffered_write, image, quality, options)
         }
         #[cfg(feature = "pnm")]
         ImageRsOutputFormat::Pnm => {
@@ -469,89 +1296,483 @@ fn into_output_format<W: std::io::Write + Seek>(
                                     Ok(pnm::PnmSubtype::Pixmap(encoding))
                                 }
                             } else {
-                                Err(Error::Term(Box::new(atoms::bad_argument())))
+                                Err(bad_option(options, "encoding"))
                             }
                         } else {
-                            Err(Error::Term(Box::new(atoms::bad_argument())))
+                            Err(error_term(atoms::bad_argument(), "option `encoding` is required for this subtype"))
                         }
                     }
                 } else {
-                    Err(Error::Term(Box::new(atoms::bad_argument())))
+                    Err(bad_option(options, "subtype"))
                 }
             } else {
-                Err(Error::Term(Box::new(atoms::bad_argument())))
+                Err(error_term(atoms::bad_argument(), "option `subtype` is required"))
             };
-            if let Ok(subtype) = subtype_result {
-                match pnm::PnmEncoder::new(buffered_write)
-                    .with_subtype(subtype)
-                    .write_image(buf, width, height, color.into())
-                {
-                    Ok(_) => Ok(()),
-                    Err(_) => Err(Error::Term(Box::new(atoms::io()))),
-                }
-            } else {
-                Err(Error::Term(Box::new(atoms::bad_argument())))
+            match pnm::PnmEncoder::new(buffered_write)
+                .with_subtype(subtype_result?)
+                .write_image(buf, width, height, color.into())
+            {
+                Ok(_) => Ok(()),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "gif")]
         ImageRsOutputFormat::Gif => {
             match gif::GifEncoder::new(buffered_write).encode(buf, width, height, color.into()) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "ico")]
         ImageRsOutputFormat::Ico => {
             match ico::IcoEncoder::new(buffered_write).write_image(buf, width, height, color.into()) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "bmp")]
         ImageRsOutputFormat::Bmp => {
             match bmp::BmpEncoder::new(buffered_write).write_image(buf, width, height, color.into()) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "tga")]
         ImageRsOutputFormat::Tga => {
             match tga::TgaEncoder::new(buffered_write).write_image(buf, width, height, color.into()) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "tiff")]
         ImageRsOutputFormat::Tiff => {
-            match tiff::TiffEncoder::new(buffered_write).write_image(buf, width, height, color.into()) {
+            let compression = tiff_compression(options)?;
+            let pages = [(&**image, image.icc_profile())];
+            match tiff_codec::encode(buffered_write, pages, compression) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "avif")]
         ImageRsOutputFormat::Avif => {
-            match avif::AvifEncoder::new(buffered_write).write_image(buf, width, height, color.into()) {
+            let speed = match options.get("speed").map(|value| value.parse::<u8>()) {
+                Some(Ok(speed)) if (1..=10).contains(&speed) => speed,
+                None => 4,
+                _ => return Err(bad_option(options, "speed")),
+            };
+            let quality = match options.get("quality").map(|value| value.parse::<u8>()) {
+                Some(Ok(quality)) if (1..=100).contains(&quality) => quality,
+                None => 80,
+                _ => return Err(bad_option(options, "quality")),
+            };
+            let threads = match options.get("threads").map(|value| value.parse::<usize>()) {
+                Some(Ok(threads)) if threads > 0 => Some(threads),
+                None => None,
+                _ => return Err(bad_option(options, "threads")),
+            };
+            match avif::AvifEncoder::new_with_speed_quality(buffered_write, speed, quality)
+                .with_num_threads(threads)
+                .write_image(buf, width, height, color.into())
+            {
+                Ok(_) => Ok(()),
+                Err(ref e) => Err(image_error_to_term(e)),
+            }
+        }
+        #[cfg(feature = "hdr")]
+        ImageRsOutputFormat::Hdr => {
+            let encoder = hdr::HdrEncoder::new(buffered_write);
+            match encoder.write_image(buf, width, height, color.into()) {
+                Ok(_) => Ok(()),
+                Err(ref e) => Err(image_error_to_term(e)),
+            }
+        }
+        #[cfg(feature = "exr")]
+        ImageRsOutputFormat::Exr => {
+            let encoder = openexr::OpenExrEncoder::new(buffered_write);
+            match encoder.write_image(buf, width, height, color.into()) {
+                Ok(_) => Ok(()),
+                Err(ref e) => Err(image_error_to_term(e)),
+            }
+        }
+        #[cfg(feature = "ff")]
+        ImageRsOutputFormat::Farbfeld => {
+            let encoder = farbfeld::FarbfeldEncoder::new(buffered_write);
+            match encoder.write_image(buf, width, height, color.into()) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "qoi")]
         ImageRsOutputFormat::Qoi => {
             match qoi::QoiEncoder::new(buffered_write).write_image(buf, width, height, color.into()) {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
+                Err(ref e) => Err(image_error_to_term(e)),
             }
         }
         #[cfg(feature = "webp")]
-        ImageRsOutputFormat::Webp => {
-            match webp::WebPEncoder::new_lossless(buffered_write)
-                .write_image(buf, width, height, color.into())
+        ImageRsOutputFormat::Webp => match options.get("lossless").map(|value| &value[..]) {
+            Some("true") | None => {
+                let mut encoder = webp::WebPEncoder::new_lossless(buffered_write);
+                embed_icc_profile(&mut encoder, image)?;
+                match encoder.write_image(buf, width, height, color.into()) {
+                    Ok(_) => Ok(()),
+                    Err(ref e) => Err(image_error_to_term(e)),
+                }?;
+                Ok(())
+            }
+            #[cfg(feature = "webp-lossy")]
+            Some("false") => {
+                let quality = match options.get("quality").map(|quality| quality.parse::<f32>()) {
+                    Some(Ok(quality)) if (0.0..=100.0).contains(&quality) => quality,
+                    None => 75.0,
+                    _ => return Err(bad_option(options, "quality")),
+                };
+                match webp_lossy::encode(buffered_write, image, quality, image.icc_profile()) {
+                    Ok(_) => Ok(()),
+                    Err(ref e) => Err(image_error_to_term(e)),
+                }
+            }
+            #[cfg(not(feature = "webp-lossy"))]
+            Some("false") => Err(error_term(
+                atoms::unsupported_format(),
+                "lossy WebP needs the `webp-lossy` feature",
+            )),
+            Some(_) => Err(bad_option(options, "lossless")),
+        },
+        // Only reachable when some encoder features are disabled.
+        #[allow(unreachable_patterns)]
+        format => Err(error_term(
+            atoms::unsupported_format(),
+            format!("{:?} encoding is not enabled", ImageFormat::from(format)),
+        )),
+    }
+}
+
+/// Converts `image` to a color type `format` can store, or returns `None` if it
+/// already has one.
+///
+/// Formats without alpha get the image flattened onto the `background` option,
+/// white by default. Otherwise the channels are kept where possible and the
+/// bit depth is reduced to the nearest one the encoder supports.
+fn encodable_image(
+    image: &ImageRsDynamicImage,
+    format: &ImageRsOutputFormat,
+    options: &HashMap<String, String>,
+) -> Result<Option<ImageRsDynamicImage>, Error> {
+    let color = image.color();
+    let pnm_subtype = options.get("subtype").map(|value| &value[..]);
+    let keeps_alpha = match format {
+        ImageRsOutputFormat::Jpeg | ImageRsOutputFormat::Hdr => false,
+        ImageRsOutputFormat::Pnm => pnm_subtype == Some("arbitrarymap"),
+        _ => true,
+    };
+    let flattened = match color.has_alpha() && !keeps_alpha {
+        true => Some(flatten_alpha(image, background(options)?)),
+        false => None,
+    };
+    let source = flattened.as_ref().unwrap_or(image);
+
+    let gray = !source.color().has_color();
+    let alpha = source.color().has_alpha();
+    let depth = color.bytes_per_pixel() / color.channel_count();
+    let target = match format {
+        ImageRsOutputFormat::Png | ImageRsOutputFormat::Ico | ImageRsOutputFormat::Avif => {
+            color_type(gray, alpha, depth.min(2))
+        }
+        ImageRsOutputFormat::Pnm => match pnm_subtype {
+            Some("pixmap") => ColorType::Rgb8,
+            Some("bitmap") | Some("graymap") => ColorType::L8,
+            // PAM has no 16-bit gray with alpha.
+            _ => color_type(gray && !(alpha && depth > 1), alpha, depth.min(2)),
+        },
+        ImageRsOutputFormat::Jpeg
+        | ImageRsOutputFormat::Bmp
+        | ImageRsOutputFormat::Tga
+        | ImageRsOutputFormat::Webp => color_type(gray, alpha, 1),
+        ImageRsOutputFormat::Gif | ImageRsOutputFormat::Qoi => color_type(false, alpha, 1),
+        ImageRsOutputFormat::Tiff => color_type(gray && !alpha, alpha, depth),
+        ImageRsOutputFormat::Exr => color_type(false, alpha, 4),
+        ImageRsOutputFormat::Farbfeld => ColorType::Rgba16,
+        ImageRsOutputFormat::Hdr => ColorType::Rgb32F,
+    };
+
+    let converted = match flattened {
+        Some(flattened) if flattened.color() == target => flattened,
+        Some(flattened) => convert_color(&flattened, target),
+        None if target == color => return Ok(None),
+        None => convert_color(image, target),
+    };
+    Ok(Some(image.derive(converted)))
+}
+
+/// The color type with the given channels and bytes per channel, with gray
+/// 32-bit float widened to RGB as `image` has no such type.
+fn color_type(gray: bool, alpha: bool, depth: u8) -> ColorType {
+    match (gray, alpha, depth) {
+        (true, false, 1) => ColorType::L8,
+        (true, true, 1) => ColorType::La8,
+        (false, false, 1) => ColorType::Rgb8,
+        (false, true, 1) => ColorType::Rgba8,
+        (true, false, 2) => ColorType::L16,
+        (true, true, 2) => ColorType::La16,
+        (false, false, 2) => ColorType::Rgb16,
+        (false, true, 2) => ColorType::Rgba16,
+        (_, false, _) => ColorType::Rgb32F,
+        (_, true, _) => ColorType::Rgba32F,
+    }
+}
+
+fn convert_color(image: &DynamicImage, color: ColorType) -> DynamicImage {
+    match color {
+        ColorType::L8 => DynamicImage::ImageLuma8(image.to_luma8()),
+        ColorType::La8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+        ColorType::Rgb8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+        ColorType::L16 => DynamicImage::ImageLuma16(image.to_luma16()),
+        ColorType::La16 => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+        ColorType::Rgb16 => DynamicImage::ImageRgb16(image.to_rgb16()),
+        ColorType::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+        ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+        ColorType::Rgba32F => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+        _ => DynamicImage::ImageRgba8(image.to_rgba8()),
+    }
+}
+
+/// Blends `image` onto an opaque `background`, keeping gray images gray when
+/// the background is gray too.
+fn flatten_alpha(image: &DynamicImage, background: [u8; 3]) -> DynamicImage {
+    let [r, g, b] = background;
+    let gray = !image.color().has_color() && r == g && g == b;
+    let depth = image.color().bytes_per_pixel() / image.color().channel_count();
+
+    let background = background.map(|value| f32::from(value) / 255.0);
+    let rgba = image.to_rgba32f();
+    let flattened = ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let Rgba([r, g, b, a]) = *rgba.get_pixel(x, y);
+        let a = a.clamp(0.0, 1.0);
+        let blend = |value: f32, background: f32| value * a + background * (1.0 - a);
+        Rgb([
+            blend(r, background[0]),
+            blend(g, background[1]),
+            blend(b, background[2]),
+        ])
+    });
+    convert_color(
+        &DynamicImage::ImageRgb32F(flattened),
+        color_type(gray, false, depth),
+    )
+}
+
+/// Parses the `background` option, given as `"r,g,b"`.
+fn background(options: &HashMap<String, String>) -> Result<[u8; 3], Error> {
+    let Some(background) = options.get("background") else {
+        return Ok([255, 255, 255]);
+    };
+    let channels: Vec<u8> = background
+        .split(',')
+        .map(|value| value.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| bad_option(options, "background"))?;
+    match channels[..] {
+        [r, g, b] => Ok([r, g, b]),
+        _ => Err(bad_option(options, "background")),
+    }
+}
+
+#[cfg(feature = "tiff")]
+fn tiff_compression(
+    options: &HashMap<String, String>,
+) -> Result<::tiff::encoder::Compression, Error> {
+    use ::tiff::encoder::Compression;
+    match options.get("compression").map(|value| &value[..]) {
+        Some("none") | None => Ok(Compression::Uncompressed),
+        Some("lzw") => Ok(Compression::Lzw),
+        Some("deflate") => Ok(Compression::Deflate(Default::default())),
+        Some("packbits") => Ok(Compression::Packbits),
+        Some(_) => Err(bad_option(options, "compression")),
+    }
+}
+
+#[cfg(feature = "jpeg-encoder")]
+fn encode_jpeg<W: std::io::Write>(
+    buffered_write: &mut W,
+    image: &ImageRsDynamicImage,
+    quality: u8,
+    options: &HashMap<String, String>,
+) -> Result<(), Error> {
+    use jpeg_encoder::SamplingFactor;
+    let bad_argument = |key| bad_option(options, key);
+    let subsampling = match options.get("subsampling").map(|value| &value[..]) {
+        None => None,
+        Some("4:4:4") => Some(SamplingFactor::R_4_4_4),
+        Some("4:2:2") => Some(SamplingFactor::R_4_2_2),
+        Some("4:2:0") => Some(SamplingFactor::R_4_2_0),
+        Some(_) => return Err(bad_argument("subsampling")),
+    };
+    let restart_interval = match options.get("restart_interval").map(|value| value.parse()) {
+        None => None,
+        Some(Ok(restart_interval)) => Some(restart_interval),
+        Some(Err(_)) => return Err(bad_argument("restart_interval")),
+    };
+    let flag = |key| options.get(key).is_some_and(|value| value == "true");
+    let jpeg_options = jpeg_codec::JpegOptions {
+        quality,
+        subsampling,
+        progressive: flag("progressive"),
+        restart_interval,
+        optimize_huffman: flag("optimize_huffman"),
+    };
+    match jpeg_codec::encode(buffered_write, image, jpeg_options, image.icc_profile()) {
+        Ok(_) => Ok(()),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+/// Without `jpeg-encoder`, only the quality can be set. `image`'s encoder
+/// always writes baseline 4:4:4 JPEGs with standard Huffman tables.
+#[cfg(all(feature = "jpeg", not(feature = "jpeg-encoder")))]
+fn encode_jpeg<W: std::io::Write>(
+    buffered_write: &mut W,
+    image: &ImageRsDynamicImage,
+    quality: u8,
+    options: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let fixed = [
+        ("subsampling", "4:4:4"),
+        ("progressive", "false"),
+        ("restart_interval", "0"),
+        ("optimize_huffman", "false"),
+    ];
+    if let Some((key, _)) = fixed
+        .iter()
+        .find(|(key, fixed)| options.get(*key).is_some_and(|value| value != fixed))
+    {
+        return Err(error_term(
+            atoms::unsupported_format(),
+            format!("the JPEG `{}` option needs the `jpeg-encoder` feature", key),
+        ));
+    }
+    let (width, height) = (image.width(), image.height());
+    let mut encoder = jpeg::JpegEncoder::new_with_quality(buffered_write, quality);
+    embed_icc_profile(&mut encoder, image)?;
+    match encoder.write_image(image.as_bytes(), width, height, image.color().into()) {
+        Ok(_) => Ok(()),
+        Err(ref e) => Err(image_error_to_term(e)),
+    }
+}
+
+#[cfg(any(
+    feature = "png",
+    feature = "webp",
+    all(feature = "jpeg", not(feature = "jpeg-encoder"))
+))]
+fn embed_icc_profile<E: ImageEncoder>(
+    encoder: &mut E,
+    image: &ImageRsDynamicImage,
+) -> Result<(), Error> {
+    match image.icc_profile() {
+        Some(icc_profile) => encoder
+            .set_icc_profile(icc_profile.to_vec())
+            .map_err(|e| image_error_to_term(&ImageError::Unsupported(e))),
+        None => Ok(()),
+    }
+}
+
+fn into_animated_output_format<W: std::io::Write>(
+    buffered_write: &mut W,
+    frames: Vec<Frame>,
+    format: ImageRsOutputFormat,
+    options: &HashMap<String, String>,
+) -> Result<(), Error> {
+    match format {
+        #[cfg(feature = "gif")]
+        ImageRsOutputFormat::Gif => {
+            let speed = match options.get("speed") {
+                Some(speed) => match speed.parse::<i32>() {
+                    Ok(speed) if (1..=30).contains(&speed) => speed,
+                    _ => return Err(bad_option(options, "speed")),
+                },
+                None => 1,
+            };
+            let repeat = match options.get("repeat").map(|repeat| &repeat[..]) {
+                Some("infinite") | None => gif::Repeat::Infinite,
+                Some(count) => match count.parse::<u16>() {
+                    Ok(count) => gif::Repeat::Finite(count),
+                    Err(_) => return Err(bad_option(options, "repeat")),
+                },
+            };
+
+            let mut encoder = gif::GifEncoder::new_with_speed(buffered_write, speed);
+            match encoder
+                .set_repeat(repeat)
+                .and_then(|_| encoder.encode_frames(frames))
+            {
+                Ok(_) => Ok(()),
+                Err(ref e) => Err(image_error_to_term(e)),
+            }
+        }
+        format => {
+            let _ = (buffered_write, frames, options);
+            Err(error_term(
+                atoms::unsupported_format(),
+                format!(
+                    "{:?} cannot be encoded as an animation",
+                    ImageFormat::from(format)
+                ),
+            ))
+        }
+    }
+}
+
+fn into_multipage_output_format<W: std::io::Write + Seek>(
+    buffered_write: &mut W,
+    images: &[ImageRsDynamicImage],
+    format: ImageRsOutputFormat,
+    options: &HashMap<String, String>,
+) -> Result<(), Error> {
+    let converted = images
+        .iter()
+        .map(|image| encodable_image(image, &format, options))
+        .collect::<Result<Vec<_>, _>>()?;
+    let images: Vec<&ImageRsDynamicImage> = images
+        .iter()
+        .zip(&converted)
+        .map(|(image, converted)| converted.as_ref().unwrap_or(image))
+        .collect();
+    match format {
+        #[cfg(feature = "tiff")]
+        ImageRsOutputFormat::Tiff => {
+            let compression = tiff_compression(options)?;
+            let pages = images.iter().map(|&image| (&**image, image.icc_profile()));
+            match tiff_codec::encode(buffered_write, pages, compression) {
+                Ok(_) => Ok(()),
+                Err(ref e) => Err(image_error_to_term(e)),
+            }
+        }
+        #[cfg(feature = "ico")]
+        ImageRsOutputFormat::Ico => {
+            let frames = images
+                .iter()
+                .map(|image| {
+                    let (width, height) = (image.width(), image.height());
+                    ico::IcoFrame::as_png(image.as_bytes(), width, height, image.color().into())
+                })
+                .collect::<ImageResult<Vec<_>>>();
+            match frames
+                .and_then(|frames| ico::IcoEncoder::new(buffered_write).encode_images(&frames))
             {
                 Ok(_) => Ok(()),
-                Err(_) => Err(Error::Term(Box::new(atoms::io()))),
-            }?;
-            Ok(())
+                Err(ref e) => Err(image_error_to_term(e)),
+            }
+        }
+        format => {
+            let _ = (buffered_write, images);
+            Err(error_term(
+                atoms::unsupported_format(),
+                format!(
+                    "{:?} cannot hold more than one image",
+                    ImageFormat::from(format)
+                ),
+            ))
         }
-        _ => Err(Error::Term(Box::new(atoms::unsupported_format()))),
     }
 }
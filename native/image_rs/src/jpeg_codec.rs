@@ -0,0 +1,75 @@
+use image::error::{
+    EncodingError, LimitError, LimitErrorKind, UnsupportedError, UnsupportedErrorKind,
+};
+use image::{ColorType, DynamicImage, ImageError, ImageFormat, ImageResult};
+use jpeg_encoder::{ColorType as JpegColorType, Encoder, SamplingFactor};
+use std::io::Write;
+
+pub struct JpegOptions {
+    /// From 1 (smallest) to 100 (best).
+    pub quality: u8,
+    /// `None` lets the encoder pick 4:2:0 below quality 90 and 4:4:4 above.
+    pub subsampling: Option<SamplingFactor>,
+    pub progressive: bool,
+    /// Number of MCUs between restart markers, `Some(0)` writes none.
+    pub restart_interval: Option<u16>,
+    pub optimize_huffman: bool,
+}
+
+/// Writes `image` as a baseline or progressive JPEG with `jpeg-encoder`,
+/// embedding `icc_profile` when given.
+///
+/// Only `L8` and `Rgb8` images are supported, so alpha has to be flattened
+/// beforehand.
+pub fn encode<W: Write>(
+    writer: &mut W,
+    image: &DynamicImage,
+    options: JpegOptions,
+    icc_profile: Option<&[u8]>,
+) -> ImageResult<()> {
+    let (width, height) = match (u16::try_from(image.width()), u16::try_from(image.height())) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            return Err(ImageError::Limits(LimitError::from_kind(
+                LimitErrorKind::DimensionError,
+            )))
+        }
+    };
+    let color_type = match image.color() {
+        ColorType::L8 => JpegColorType::Luma,
+        ColorType::Rgb8 => JpegColorType::Rgb,
+        color => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormat::Jpeg.into(),
+                    UnsupportedErrorKind::Color(color.into()),
+                ),
+            ))
+        }
+    };
+
+    let mut encoder = Encoder::new(writer, options.quality.max(1));
+    if let Some(subsampling) = options.subsampling {
+        encoder.set_sampling_factor(subsampling);
+    }
+    encoder.set_progressive(options.progressive);
+    if let Some(restart_interval) = options.restart_interval {
+        encoder.set_restart_interval(restart_interval);
+    }
+    encoder.set_optimized_huffman_tables(options.optimize_huffman);
+    if let Some(icc_profile) = icc_profile {
+        encoder
+            .add_icc_profile(icc_profile)
+            .map_err(from_jpeg_error)?;
+    }
+    encoder
+        .encode(image.as_bytes(), width, height, color_type)
+        .map_err(from_jpeg_error)
+}
+
+fn from_jpeg_error(err: jpeg_encoder::EncodingError) -> ImageError {
+    match err {
+        jpeg_encoder::EncodingError::IoError(err) => ImageError::IoError(err),
+        err => ImageError::Encoding(EncodingError::new(ImageFormat::Jpeg.into(), err)),
+    }
+}
@@ -1,17 +1,27 @@
 use rustler::{Env, Term};
 
+#[cfg(feature = "cms")]
+mod cms;
 mod datatypes;
 mod image_rs;
+#[cfg(feature = "jpeg-encoder")]
+mod jpeg_codec;
+mod metadata;
+#[cfg(feature = "tiff")]
+mod tiff_codec;
+mod tonemap;
+#[cfg(feature = "webp-lossy")]
+mod webp_lossy;
 
 pub use datatypes::{
-    ImageRsColorType, ImageRsDataType, ImageRsDynamicImage, ImageRsDynamicImageRef,
-    ImageRsFilterType, ImageRsOutputFormat,
+    ImageRsColorType, ImageRsDataType, ImageRsDisposalMethod, ImageRsDynamicImage,
+    ImageRsDynamicImageRef, ImageRsError, ImageRsFilterType, ImageRsFormat, ImageRsFrame,
+    ImageRsInfo, ImageRsMetadata, ImageRsOutputFormat, ImageRsTonemapOperator,
 };
 pub use image_rs::*;
 
 fn on_load(env: Env, _info: Term) -> bool {
-    let _ = rustler::resource!(ImageRsDynamicImageRef, env);
-    true
+    env.register::<ImageRsDynamicImageRef>().is_ok()
 }
 
 mod atoms {
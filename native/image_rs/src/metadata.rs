@@ -0,0 +1,78 @@
+use exif::{Exif, In, Reader, Tag, Value};
+
+/// Commonly used tags parsed out of an EXIF blob.
+#[derive(Default)]
+pub struct ExifTags {
+    pub orientation: Option<u32>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub datetime: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub altitude: Option<f64>,
+}
+
+/// Parses a raw EXIF blob in TIFF layout, with or without the `Exif\0\0` marker.
+///
+/// Malformed data yields empty tags rather than an error, as metadata is
+/// only ever supplementary to the image itself.
+pub fn parse_exif(raw: &[u8]) -> ExifTags {
+    let raw = raw.strip_prefix(b"Exif\0\0").unwrap_or(raw);
+    match Reader::new().read_raw(raw.to_vec()) {
+        Ok(exif) => ExifTags {
+            orientation: exif
+                .get_field(Tag::Orientation, In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+                .filter(|orientation| (1..=8).contains(orientation)),
+            make: ascii_field(&exif, Tag::Make),
+            model: ascii_field(&exif, Tag::Model),
+            datetime: ascii_field(&exif, Tag::DateTimeOriginal)
+                .or_else(|| ascii_field(&exif, Tag::DateTime)),
+            latitude: gps_coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+            longitude: gps_coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+            altitude: gps_altitude(&exif),
+        },
+        Err(_) => ExifTags::default(),
+    }
+}
+
+fn ascii_field(exif: &Exif, tag: Tag) -> Option<String> {
+    match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+        Some(Value::Ascii(values)) => values
+            .first()
+            .map(|value| String::from_utf8_lossy(value).trim_end().to_string()),
+        _ => None,
+    }
+}
+
+/// Converts a degrees/minutes/seconds triple into signed decimal degrees.
+fn gps_coordinate(exif: &Exif, tag: Tag, ref_tag: Tag, negative_ref: &str) -> Option<f64> {
+    let degrees = match exif.get_field(tag, In::PRIMARY).map(|field| &field.value) {
+        Some(Value::Rational(dms)) if dms.len() == 3 => {
+            dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0
+        }
+        _ => return None,
+    };
+    match ascii_field(exif, ref_tag) {
+        Some(reference) if reference == negative_ref => Some(-degrees),
+        _ => Some(degrees),
+    }
+}
+
+fn gps_altitude(exif: &Exif) -> Option<f64> {
+    let altitude = match exif
+        .get_field(Tag::GPSAltitude, In::PRIMARY)
+        .map(|field| &field.value)
+    {
+        Some(Value::Rational(values)) => values.first()?.to_f64(),
+        _ => return None,
+    };
+    // A reference of 1 means the altitude is below sea level.
+    match exif
+        .get_field(Tag::GPSAltitudeRef, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+    {
+        Some(1) => Some(-altitude),
+        _ => Some(altitude),
+    }
+}
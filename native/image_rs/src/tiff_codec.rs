@@ -0,0 +1,259 @@
+use image::error::{DecodingError, EncodingError, UnsupportedError, UnsupportedErrorKind};
+use image::{DynamicImage, ImageBuffer, ImageError, ImageFormat, ImageResult, Limits};
+use std::io::{Read, Seek, SeekFrom, Write};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::encoder::colortype::{
+    ColorType, Gray16, Gray8, RGB32Float, RGBA32Float, RGB16, RGB8, RGBA16, RGBA8,
+};
+use tiff::encoder::{Compression, TiffEncoder, TiffValue};
+use tiff::tags::Tag;
+use tiff::{TiffError, TiffResult};
+
+/// Writes each `(image, icc_profile)` of `pages` as a page of one TIFF file.
+///
+/// `image`'s own `TiffEncoder` can neither carry an ICC profile, compress nor
+/// write more than one page, so this goes through the `tiff` crate directly.
+pub fn encode<'a, W: Write + Seek>(
+    writer: W,
+    pages: impl IntoIterator<Item = (&'a DynamicImage, Option<&'a [u8]>)>,
+    compression: Compression,
+) -> ImageResult<()> {
+    let mut encoder = TiffEncoder::new(writer)
+        .map_err(from_tiff_encoding_error)?
+        .with_compression(compression);
+    for (image, icc_profile) in pages {
+        encode_page(&mut encoder, image, icc_profile)?;
+    }
+    Ok(())
+}
+
+fn encode_page<W: Write + Seek>(
+    encoder: &mut TiffEncoder<W>,
+    image: &DynamicImage,
+    icc_profile: Option<&[u8]>,
+) -> ImageResult<()> {
+    let (width, height) = (image.width(), image.height());
+    match image {
+        DynamicImage::ImageLuma8(buffer) => {
+            write_page::<_, Gray8>(encoder, width, height, buffer, icc_profile)
+        }
+        DynamicImage::ImageRgb8(buffer) => {
+            write_page::<_, RGB8>(encoder, width, height, buffer, icc_profile)
+        }
+        DynamicImage::ImageRgba8(buffer) => {
+            write_page::<_, RGBA8>(encoder, width, height, buffer, icc_profile)
+        }
+        DynamicImage::ImageLuma16(buffer) => {
+            write_page::<_, Gray16>(encoder, width, height, buffer, icc_profile)
+        }
+        DynamicImage::ImageRgb16(buffer) => {
+            write_page::<_, RGB16>(encoder, width, height, buffer, icc_profile)
+        }
+        DynamicImage::ImageRgba16(buffer) => {
+            write_page::<_, RGBA16>(encoder, width, height, buffer, icc_profile)
+        }
+        DynamicImage::ImageRgb32F(buffer) => {
+            write_page::<_, RGB32Float>(encoder, width, height, buffer, icc_profile)
+        }
+        DynamicImage::ImageRgba32F(buffer) => {
+            write_page::<_, RGBA32Float>(encoder, width, height, buffer, icc_profile)
+        }
+        _ => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormat::Tiff.into(),
+                    UnsupportedErrorKind::Color(image.color().into()),
+                ),
+            ))
+        }
+    }
+    .map_err(from_tiff_encoding_error)
+}
+
+fn write_page<W: Write + Seek, C: ColorType>(
+    encoder: &mut TiffEncoder<W>,
+    width: u32,
+    height: u32,
+    data: &[C::Inner],
+    icc_profile: Option<&[u8]>,
+) -> TiffResult<()>
+where
+    [C::Inner]: TiffValue,
+{
+    let mut page = encoder.new_image::<C>(width, height)?;
+    if let Some(icc_profile) = icc_profile {
+        page.encoder().write_tag(Tag::IccProfile, icc_profile)?;
+    }
+    page.write_data(data)
+}
+
+/// Reads the ICC profile of the first TIFF page, leaving `reader` where it was.
+///
+/// `image`'s `TiffDecoder` looks the profile up under the wrong tag and never
+/// finds it.
+pub fn read_icc_profile<R: Read + Seek>(reader: &mut R) -> ImageResult<Option<Vec<u8>>> {
+    let start = reader.stream_position()?;
+    let icc_profile = Decoder::new(&mut *reader)
+        .ok()
+        .and_then(|mut decoder| decoder.get_tag_u8_vec(Tag::IccProfile).ok());
+    reader.seek(SeekFrom::Start(start))?;
+    Ok(icc_profile)
+}
+
+/// Decodes every page of a TIFF file along with its ICC profile.
+///
+/// All pages are kept in memory, so `limits` applies to each page's dimensions
+/// and to the bytes of all pages together.
+pub fn decode_pages<R: Read + Seek>(
+    reader: R,
+    mut limits: Limits,
+) -> ImageResult<Vec<(DynamicImage, Option<Vec<u8>>)>> {
+    let mut tiff_limits = tiff::decoder::Limits::default();
+    if let Some(max_alloc) = limits.max_alloc {
+        tiff_limits.decoding_buffer_size = usize::try_from(max_alloc).unwrap_or(usize::MAX);
+    }
+    let mut decoder = Decoder::new(reader)
+        .map_err(from_tiff_decoding_error)?
+        .with_limits(tiff_limits);
+    let mut pages = vec![decode_page(&mut decoder, &mut limits)?];
+    while decoder.more_images() {
+        decoder.next_image().map_err(from_tiff_decoding_error)?;
+        pages.push(decode_page(&mut decoder, &mut limits)?);
+    }
+    Ok(pages)
+}
+
+/// Counts the pages of a TIFF file without decoding any pixels.
+pub fn count_pages<R: Read + Seek>(reader: R) -> ImageResult<u32> {
+    let mut decoder = Decoder::new(reader).map_err(from_tiff_decoding_error)?;
+    let mut pages = 1;
+    while decoder.more_images() {
+        decoder.next_image().map_err(from_tiff_decoding_error)?;
+        pages += 1;
+    }
+    Ok(pages)
+}
+
+fn decode_page<R: Read + Seek>(
+    decoder: &mut Decoder<R>,
+    limits: &mut Limits,
+) -> ImageResult<(DynamicImage, Option<Vec<u8>>)> {
+    use tiff::ColorType as TiffColorType;
+
+    let (width, height) = decoder.dimensions().map_err(from_tiff_decoding_error)?;
+    let color_type = decoder.colortype().map_err(from_tiff_decoding_error)?;
+    limits.check_dimensions(width, height)?;
+    limits.reserve(page_bytes(width, height, color_type))?;
+    let icc_profile = decoder.get_tag_u8_vec(Tag::IccProfile).ok();
+    let data = decoder.read_image().map_err(from_tiff_decoding_error)?;
+    let image = match (color_type, data) {
+        (TiffColorType::Gray(1), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, expand_bilevel(width, &data))
+                .map(DynamicImage::ImageLuma8)
+        }
+        (TiffColorType::Gray(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+        }
+        (TiffColorType::Gray(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma16)
+        }
+        (TiffColorType::GrayA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA8)
+        }
+        (TiffColorType::GrayA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLumaA16)
+        }
+        (TiffColorType::RGB(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+        }
+        (TiffColorType::RGB(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb16)
+        }
+        (TiffColorType::RGB(32), DecodingResult::F32(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb32F)
+        }
+        (TiffColorType::RGBA(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+        }
+        (TiffColorType::RGBA(16), DecodingResult::U16(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba16)
+        }
+        (TiffColorType::RGBA(32), DecodingResult::F32(data)) => {
+            ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba32F)
+        }
+        (TiffColorType::CMYK(8), DecodingResult::U8(data)) => {
+            ImageBuffer::from_raw(width, height, cmyk_to_rgb(&data)).map(DynamicImage::ImageRgb8)
+        }
+        _ => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormat::Tiff.into(),
+                    UnsupportedErrorKind::GenericFeature(format!("{color_type:?}")),
+                ),
+            ))
+        }
+    };
+    let image = image.ok_or_else(|| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormat::Tiff.into(),
+            "page data does not match its dimensions",
+        ))
+    })?;
+    Ok((image, icc_profile))
+}
+
+/// Bytes of a decoded page, counting each sample as at least one byte.
+fn page_bytes(width: u32, height: u32, color_type: tiff::ColorType) -> u64 {
+    use tiff::ColorType as TiffColorType;
+
+    let (samples, bit_depth) = match color_type {
+        TiffColorType::Gray(bits) | TiffColorType::Palette(bits) => (1, bits),
+        TiffColorType::GrayA(bits) => (2, bits),
+        TiffColorType::RGB(bits) | TiffColorType::YCbCr(bits) => (3, bits),
+        TiffColorType::RGBA(bits) | TiffColorType::CMYK(bits) => (4, bits),
+        TiffColorType::CMYKA(bits) => (5, bits),
+        TiffColorType::Multiband {
+            bit_depth,
+            num_samples,
+        } => (num_samples, bit_depth),
+        _ => (1, 8),
+    };
+    u64::from(width) * u64::from(height) * u64::from(samples) * u64::from(bit_depth).div_ceil(8)
+}
+
+/// Expands 1-bit rows, padded to whole bytes, to one byte per pixel.
+fn expand_bilevel(width: u32, data: &[u8]) -> Vec<u8> {
+    let row_bytes = width.div_ceil(8) as usize;
+    data.chunks_exact(row_bytes)
+        .flat_map(|row| {
+            (0..width as usize).map(move |x| match row[x / 8] >> (7 - x % 8) & 1 {
+                0 => 0,
+                _ => 255,
+            })
+        })
+        .collect()
+}
+
+/// Multiplies out CMYK samples the same way `image`'s `TiffDecoder` does.
+fn cmyk_to_rgb(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(4)
+        .flat_map(|cmyk| {
+            let k = 1. - f32::from(cmyk[3]) / 255.;
+            [0, 1, 2].map(|i| ((255. - f32::from(cmyk[i])) * k) as u8)
+        })
+        .collect()
+}
+
+fn from_tiff_decoding_error(err: TiffError) -> ImageError {
+    match err {
+        TiffError::IoError(err) => ImageError::IoError(err),
+        err => ImageError::Decoding(DecodingError::new(ImageFormat::Tiff.into(), err)),
+    }
+}
+
+fn from_tiff_encoding_error(err: TiffError) -> ImageError {
+    match err {
+        TiffError::IoError(err) => ImageError::IoError(err),
+        err => ImageError::Encoding(EncodingError::new(ImageFormat::Tiff.into(), err)),
+    }
+}
@@ -0,0 +1,50 @@
+use crate::ImageRsTonemapOperator;
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba};
+
+/// Maps the linear samples of an `Rgb32F` or `Rgba32F` image into an 8-bit
+/// image, or returns `None` for any other color type.
+///
+/// `exposure` is in stops and scales the samples before `operator` compresses
+/// them into `[0, 1]`; the result is then encoded with `1 / gamma`. Alpha is
+/// only clamped.
+pub fn tonemap(
+    image: &DynamicImage,
+    operator: ImageRsTonemapOperator,
+    exposure: f32,
+    gamma: f32,
+) -> Option<DynamicImage> {
+    let scale = exposure.exp2();
+    let map = |value: f32| {
+        let value = (value * scale).max(0.0);
+        let mapped = match operator {
+            ImageRsTonemapOperator::Reinhard => value / (1.0 + value),
+            ImageRsTonemapOperator::AcesFilmic => {
+                (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+            }
+            ImageRsTonemapOperator::Exposure => value,
+        };
+        to_u8(mapped.clamp(0.0, 1.0).powf(gamma.recip()))
+    };
+
+    match image {
+        DynamicImage::ImageRgb32F(buffer) => Some(DynamicImage::ImageRgb8(ImageBuffer::from_fn(
+            buffer.width(),
+            buffer.height(),
+            |x, y| Rgb(buffer.get_pixel(x, y).0.map(map)),
+        ))),
+        DynamicImage::ImageRgba32F(buffer) => Some(DynamicImage::ImageRgba8(ImageBuffer::from_fn(
+            buffer.width(),
+            buffer.height(),
+            |x, y| {
+                let Rgba([r, g, b, a]) = *buffer.get_pixel(x, y);
+                let [r, g, b] = [r, g, b].map(map);
+                Rgba([r, g, b, to_u8(a.clamp(0.0, 1.0))])
+            },
+        ))),
+        _ => None,
+    }
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value * 255.0).round() as u8
+}
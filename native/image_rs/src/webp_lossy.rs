@@ -0,0 +1,95 @@
+use image::error::{EncodingError, UnsupportedError, UnsupportedErrorKind};
+use image::{ColorType, DynamicImage, ImageError, ImageFormat, ImageResult};
+use std::io::Write;
+use webp::Encoder;
+
+const ICC_PROFILE_FLAG: u8 = 0x20;
+
+/// Writes `image` as a lossy WebP with libwebp, embedding `icc_profile` when given.
+///
+/// `quality` goes from 0 (smallest) to 100 (best).
+pub fn encode<W: Write>(
+    writer: &mut W,
+    image: &DynamicImage,
+    quality: f32,
+    icc_profile: Option<&[u8]>,
+) -> ImageResult<()> {
+    let (width, height) = (image.width(), image.height());
+    let converted = match image.color() {
+        ColorType::L8 => Some(DynamicImage::ImageRgb8(image.to_rgb8())),
+        ColorType::La8 => Some(DynamicImage::ImageRgba8(image.to_rgba8())),
+        _ => None,
+    };
+    let image = converted.as_ref().unwrap_or(image);
+    let encoder = match image.color() {
+        ColorType::Rgb8 => Encoder::from_rgb(image.as_bytes(), width, height),
+        ColorType::Rgba8 => Encoder::from_rgba(image.as_bytes(), width, height),
+        color => {
+            return Err(ImageError::Unsupported(
+                UnsupportedError::from_format_and_kind(
+                    ImageFormat::WebP.into(),
+                    UnsupportedErrorKind::Color(color.into()),
+                ),
+            ))
+        }
+    };
+    let encoded = encoder.encode_simple(false, quality).map_err(|err| {
+        ImageError::Encoding(EncodingError::new(
+            ImageFormat::WebP.into(),
+            format!("{err:?}"),
+        ))
+    })?;
+
+    match icc_profile {
+        Some(icc_profile) => {
+            writer.write_all(&with_icc_profile(&encoded, icc_profile, width, height))?
+        }
+        None => writer.write_all(&encoded)?,
+    }
+    Ok(())
+}
+
+/// Adds an `ICCP` chunk to a WebP file written by libwebp, switching it to the
+/// extended format if needed.
+///
+/// The `VP8X` header must come first and the `ICCP` chunk right after it.
+/// Files that are too short to hold a header, or already carry a profile, are
+/// returned unchanged.
+fn with_icc_profile(webp: &[u8], icc_profile: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let Some(chunks) = webp.get(12..) else {
+        return webp.to_vec();
+    };
+    let mut body = b"WEBP".to_vec();
+    let rest = if chunks.starts_with(b"VP8X") {
+        let mut vp8x = match chunks.get(..18) {
+            Some(vp8x) if vp8x[8] & ICC_PROFILE_FLAG == 0 => vp8x.to_vec(),
+            _ => return webp.to_vec(),
+        };
+        vp8x[8] |= ICC_PROFILE_FLAG;
+        body.extend_from_slice(&vp8x);
+        &chunks[18..]
+    } else {
+        let mut vp8x = [0u8; 10];
+        vp8x[0] = ICC_PROFILE_FLAG;
+        vp8x[4..7].copy_from_slice(&(width - 1).to_le_bytes()[..3]);
+        vp8x[7..10].copy_from_slice(&(height - 1).to_le_bytes()[..3]);
+        write_chunk(&mut body, b"VP8X", &vp8x);
+        chunks
+    };
+    write_chunk(&mut body, b"ICCP", icc_profile);
+    body.extend_from_slice(rest);
+
+    let mut riff = b"RIFF".to_vec();
+    riff.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    riff.extend_from_slice(&body);
+    riff
+}
+
+fn write_chunk(buffer: &mut Vec<u8>, fourcc: &[u8; 4], payload: &[u8]) {
+    buffer.extend_from_slice(fourcc);
+    buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        buffer.push(0);
+    }
+}
@@ -4,7 +4,7 @@ defmodule ImageRs.Test do
 
   describe "decode image" do
     test "from invalid png file" do
-      {:error, :enoent} = ImageRs.from_file(Path.join(__DIR__, "test23.png"))
+      {:error, {:enoent, _}} = ImageRs.from_file(Path.join(__DIR__, "test23.png"))
     end
 
     test "from png file" do
@@ -15,6 +15,7 @@ defmodule ImageRs.Test do
       assert :rgba == image.color_type
       assert :u8 == image.dtype
       assert [2, 3, 4] == image.shape
+      assert :png == image.format
 
       assert <<241, 145, 126, 255, 136, 190, 78, 255, 68, 122, 183, 255, 244, 196, 187, 255, 190,
                205, 145, 255, 144, 184, 200, 255>> == ImageRs.to_binary(image)
@@ -65,6 +66,116 @@ defmodule ImageRs.Test do
       #          124>> == data
     end
 
+    test "from png file within limits" do
+      %ImageRs{} =
+        ImageRs.from_file(Path.join(__DIR__, "test.png"),
+          max_width: 3,
+          max_height: 2,
+          max_alloc: 1_000_000
+        )
+    end
+
+    test "from corrupt png data" do
+      {:ok, data} = File.read(Path.join(__DIR__, "test.png"))
+      <<head::binary-size(20), byte, rest::binary>> = data
+      corrupt = <<head::binary, Bitwise.bxor(byte, 0xFF), rest::binary>>
+
+      {:error, {:decoding_error, %{format: :png, message: message}}} =
+        ImageRs.from_binary(corrupt)
+
+      assert message =~ "CRC"
+      assert_raise RuntimeError, message, fn -> ImageRs.from_binary!(corrupt) end
+    end
+
+    test "from png file exceeding dimension limits" do
+      {:error, {:dimension_error, _}} =
+        ImageRs.from_file(Path.join(__DIR__, "test.png"), max_width: 1)
+    end
+
+    test "from jpg data exceeding allocation limit" do
+      {:ok, data} = File.read(Path.join(__DIR__, "test.jpg"))
+      {:error, {:insufficient_memory, _}} = ImageRs.from_binary(data, max_alloc: 1)
+    end
+
+    test "from tga data with format hint" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      tga = ImageRs.encode_as(image, :tga)
+
+      {:error, {:unsupported_format, _}} = ImageRs.from_binary(tga)
+      %ImageRs{} = decoded = ImageRs.from_binary(tga, format: :tga)
+      assert [2, 3, 4] == decoded.shape
+      assert ImageRs.to_binary(image) == ImageRs.to_binary(decoded)
+    end
+
+    test "from file with format hint" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"), format: :png)
+      assert [2, 3, 4] == image.shape
+      {:error, _} = ImageRs.from_file(Path.join(__DIR__, "test.png"), format: :jpeg)
+    end
+
+    test "from binary with invalid format hint" do
+      {:ok, data} = File.read(Path.join(__DIR__, "test.png"))
+      {:error, _} = ImageRs.from_binary(data, format: :svg)
+    end
+
+    test "source format" do
+      {:ok, data} = File.read(Path.join(__DIR__, "test.jpg"))
+      %ImageRs{} = image = ImageRs.from_binary(data)
+      assert :jpeg == image.format
+
+      %ImageRs{} = resized = ImageRs.resize(image, 4, 6)
+      assert :jpeg == resized.format
+
+      [frame | _] = ImageRs.frames_from_file(Path.join(__DIR__, "test_animated.gif"))
+      assert :gif == frame.image.format
+
+      %ImageRs{} = new_image = ImageRs.new(1, 1, :l, :u8, <<0>>)
+      assert nil == new_image.format
+    end
+
+    test "new with every color type and data type" do
+      for {color_type, channels} <- [l: 1, la: 2, rgb: 3, rgba: 4],
+          {dtype, size} <- [u8: 1, u16: 2, f32: 4] do
+        data = :binary.copy(<<0>>, 2 * 3 * channels * size)
+        %ImageRs{} = image = ImageRs.new(2, 3, color_type, dtype, data)
+        assert dtype == image.dtype
+        assert 2 == image.height
+        assert 3 == image.width
+        assert image.channels > 0
+      end
+    end
+
+    test "new with f32 grayscale data" do
+      data = <<0.25::float-32-native, 0.5::float-32-native>>
+      %ImageRs{} = image = ImageRs.new(1, 1, :la, :f32, data)
+      assert :rgba == image.color_type
+      assert [1, 1, 4] == image.shape
+
+      expected = for value <- [0.25, 0.25, 0.25, 0.5], into: <<>>, do: <<value::float-32-native>>
+      assert expected == ImageRs.to_binary(image)
+
+      %ImageRs{} = image = ImageRs.new(1, 2, :l, :f32, <<0::64>>)
+      assert :rgb == image.color_type
+      assert [1, 2, 3] == image.shape
+    end
+
+    test "new with unknown color type or data type" do
+      {:error, {:unsupported_color_type, _}} = ImageRs.new(1, 1, :unknown, :u8, <<0>>)
+      {:error, {:unsupported_image_data, _}} = ImageRs.new(1, 1, :l, :unknown, <<0>>)
+      {:error, {:invalid_image_data, _}} = ImageRs.new(1, 1, :l, :f32, <<0>>)
+    end
+
+    test "from jpg file with auto_orient" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test_exif.jpg"))
+      assert [2, 3, 3] == image.shape
+
+      %ImageRs{} =
+        oriented = ImageRs.from_file(Path.join(__DIR__, "test_exif.jpg"), auto_orient: true)
+
+      assert [3, 2, 3] == oriented.shape
+      assert ImageRs.to_binary(ImageRs.rotate90(image)) == ImageRs.to_binary(oriented)
+    end
+
     test "to_binary with png" do
       {:ok, data} = File.read(Path.join(__DIR__, "test.png"))
       %ImageRs{} = image = ImageRs.from_binary(data)
@@ -85,6 +196,615 @@ defmodule ImageRs.Test do
     end
   end
 
+  describe "probe image" do
+    test "probe png file" do
+      %ImageRs.Info{} = info = ImageRs.probe_file(Path.join(__DIR__, "test.png"))
+      assert :png == info.format
+      assert 3 == info.width
+      assert 2 == info.height
+      assert :rgba == info.color_type
+      assert :u8 == info.dtype
+      assert 1 == info.frames
+    end
+
+    test "probe jpg data" do
+      {:ok, data} = File.read(Path.join(__DIR__, "test.jpg"))
+      %ImageRs.Info{} = info = ImageRs.probe_binary(data)
+      assert :jpeg == info.format
+      assert {3, 2} == {info.width, info.height}
+      assert :rgb == info.color_type
+      assert :u8 == info.dtype
+    end
+
+    test "probe animated gif" do
+      %ImageRs.Info{} = info = ImageRs.probe_file(Path.join(__DIR__, "test_animated.gif"))
+      assert :gif == info.format
+      assert 2 == info.frames
+    end
+
+    test "probe multi-page tiff" do
+      %ImageRs.Info{} = info = ImageRs.probe_file(Path.join(__DIR__, "test_pages.tiff"))
+      assert :tiff == info.format
+      assert 2 == info.frames
+    end
+
+    test "probe invalid file" do
+      {:error, {:enoent, _}} = ImageRs.probe_file(Path.join(__DIR__, "test23.png"))
+    end
+  end
+
+  describe "read metadata" do
+    test "exif from jpg file" do
+      %ImageRs.Metadata{} =
+        metadata = ImageRs.metadata_from_file(Path.join(__DIR__, "test_exif.jpg"))
+
+      assert is_binary(metadata.exif)
+      assert 6 == metadata.orientation
+      assert "ImageRs" == metadata.make
+      assert "Test Camera" == metadata.model
+      assert "2024:01:02 03:04:05" == metadata.datetime
+      assert 35.5 == metadata.latitude
+      assert -139.75 == metadata.longitude
+      assert 62.5 == metadata.altitude
+    end
+
+    test "no exif in png data" do
+      {:ok, data} = File.read(Path.join(__DIR__, "test.png"))
+      %ImageRs.Metadata{} = metadata = ImageRs.metadata_from_binary(data)
+      assert nil == metadata.exif
+      assert nil == metadata.orientation
+      assert nil == metadata.make
+    end
+
+    test "metadata from invalid file" do
+      {:error, {:enoent, _}} = ImageRs.metadata_from_file(Path.join(__DIR__, "test23.jpg"))
+    end
+  end
+
+  describe "icc profile" do
+    test "from png file" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test_icc.png"))
+      icc_profile = ImageRs.icc_profile(image)
+      assert 598 == byte_size(icc_profile)
+      assert "acsp" == binary_part(icc_profile, 36, 4)
+    end
+
+    test "no profile" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      assert nil == ImageRs.icc_profile(image)
+
+      %ImageRs{} = image = ImageRs.new(1, 1, :rgb, :u8, <<0, 0, 0>>)
+      assert nil == ImageRs.icc_profile(image)
+    end
+
+    for format <- [:png, :jpeg, :tiff, :webp] do
+      test "preserved through resize and encode_as #{inspect(format)}" do
+        %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test_icc.png"))
+        icc_profile = ImageRs.icc_profile(image)
+        %ImageRs{} = resized = ImageRs.resize(image, 4, 6, :nearest)
+        assert icc_profile == ImageRs.icc_profile(resized)
+
+        options = if unquote(format) == :jpeg, do: [quality: 90], else: []
+        encoded = ImageRs.encode_as(resized, unquote(format), options)
+        %ImageRs{} = decoded = ImageRs.from_binary(encoded)
+        assert icc_profile == ImageRs.icc_profile(decoded)
+      end
+    end
+
+    test "convert_to_srgb" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test_icc_p3.png"))
+      %ImageRs{} = converted = ImageRs.convert_to_srgb(image)
+      assert nil == ImageRs.icc_profile(converted)
+      assert image.shape == converted.shape
+      assert :png == converted.format
+
+      <<primaries_gray_and_white::binary-size(15), r, g, b>> = ImageRs.to_binary(converted)
+      assert <<255, 0, 0, 0, 255, 0, 0, 0, 255, 128, 128, 128, 255, 255, 255>> ==
+               primaries_gray_and_white
+
+      assert abs(r - 215) <= 1
+      assert abs(g - 93) <= 1
+      assert abs(b - 31) <= 1
+    end
+
+    test "convert_to_srgb without profile" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      %ImageRs{} = converted = ImageRs.convert_to_srgb(image)
+      assert ImageRs.to_binary(image) == ImageRs.to_binary(converted)
+    end
+
+    test "dropped by grayscale" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test_icc.png"))
+      %ImageRs{} = gray = ImageRs.grayscale(image)
+      assert nil == ImageRs.icc_profile(gray)
+    end
+  end
+
+  describe "decode animated image" do
+    test "frames from gif file" do
+      [first, second] = ImageRs.frames_from_file(Path.join(__DIR__, "test_animated.gif"))
+      assert %ImageRs.Frame{image: %ImageRs{}} = first
+      assert [2, 3, 4] == first.image.shape
+      assert :rgba == first.image.color_type
+      assert {100, 1} == first.delay
+      assert {200, 1} == second.delay
+      assert {0, 0} == {first.left, first.top}
+      assert :background == first.disposal
+      assert <<255, 0, 0, 255>> == binary_part(ImageRs.to_binary(first.image), 0, 4)
+      assert <<0, 0, 255, 255>> == binary_part(ImageRs.to_binary(second.image), 0, 4)
+    end
+
+    test "frames from gif data" do
+      {:ok, data} = File.read(Path.join(__DIR__, "test_animated.gif"))
+      frames = ImageRs.frames_from_binary(data)
+      assert 2 == Enum.count(frames)
+    end
+
+    test "frames from apng file" do
+      path = Path.join(__DIR__, "test_animated.png")
+      assert 2 == ImageRs.probe_file(path).frames
+
+      [first, second] = ImageRs.frames_from_file(path)
+      assert :png == first.image.format
+      assert [4, 4, 4] == first.image.shape
+      assert {100, 1} == first.delay
+      assert {200, 1} == second.delay
+      assert {0, 0, :none} == {first.left, first.top, first.disposal}
+      assert {1, 2, :background} == {second.left, second.top, second.disposal}
+      assert <<255, 0, 0, 255>> == binary_part(ImageRs.to_binary(first.image), 0, 4)
+      # the second frame covers x in 1..2 and y in 2..3 of the canvas
+      assert <<0, 0, 255, 255>> == binary_part(ImageRs.to_binary(second.image), 52, 4)
+    end
+
+    test "frames from apng file with a default image outside the animation" do
+      path = Path.join(__DIR__, "test_animated_separate_default.png")
+      assert 2 == ImageRs.probe_file(path).frames
+
+      # the green default image is skipped
+      [first, second] = ImageRs.frames_from_file(path)
+      assert {100, 1} == first.delay
+      assert {200, 1} == second.delay
+      assert {0, 0, :none} == {first.left, first.top, first.disposal}
+      assert {1, 2, :background} == {second.left, second.top, second.disposal}
+      assert <<255, 0, 0, 255>> == binary_part(ImageRs.to_binary(first.image), 0, 4)
+    end
+
+    test "frames from animated webp file" do
+      path = Path.join(__DIR__, "test_animated.webp")
+      assert 2 == ImageRs.probe_file(path).frames
+
+      [first, second] = ImageRs.frames_from_file(path)
+      assert :webp == first.image.format
+      assert [4, 4, 4] == second.image.shape
+      assert {100, 1} == first.delay
+      assert {200, 1} == second.delay
+
+      # frames come composited onto the canvas, without their own placement
+      assert {0, 0, nil} == {second.left, second.top, second.disposal}
+      pixels = ImageRs.to_binary(second.image)
+      assert <<255, 0, 0, 255>> == binary_part(pixels, 0, 4)
+      assert <<0, 0, 255, 255>> == binary_part(pixels, 60, 4)
+    end
+
+    test "frames from still image" do
+      [frame] = ImageRs.frames_from_file(Path.join(__DIR__, "test.png"))
+      assert [2, 3, 4] == frame.image.shape
+      assert {0, 1} == frame.delay
+      assert nil == frame.disposal
+    end
+
+    test "frames from invalid file" do
+      {:error, {:enoent, _}} = ImageRs.frames_from_file(Path.join(__DIR__, "test23.gif"))
+    end
+  end
+
+  describe "decode multi-page image" do
+    test "pages from tiff file" do
+      [first, second] = ImageRs.pages_from_file(Path.join(__DIR__, "test_pages.tiff"))
+      assert [2, 10, 1] == first.shape
+      assert :l == first.color_type
+      assert :tiff == first.format
+
+      # a bilevel WhiteIsZero page, as written by fax software
+      assert <<0, 255, 255, 255, 255, 255, 255, 255, 255, 0>> ==
+               binary_part(ImageRs.to_binary(first), 0, 10)
+
+      assert [1, 2, 3] == second.shape
+      assert <<255, 0, 0, 0, 0, 255>> == ImageRs.to_binary(second)
+    end
+
+    test "pages from tiff data" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      gray = ImageRs.grayscale(ImageRs.from_file(Path.join(__DIR__, "test.jpg")))
+      tiff = ImageRs.encode_pages([image, gray, image], :tiff, compression: :deflate)
+
+      [first, second, third] = ImageRs.pages_from_binary(tiff)
+      assert ImageRs.to_binary(image) == ImageRs.to_binary(first)
+      assert ImageRs.to_binary(gray) == ImageRs.to_binary(second)
+      assert ImageRs.to_binary(image) == ImageRs.to_binary(third)
+    end
+
+    test "pages with limits" do
+      path = Path.join(__DIR__, "test_pages.tiff")
+      {:error, {:dimension_error, _}} = ImageRs.pages_from_file(path, max_width: 5)
+
+      # 20 bytes for the first page and 6 for the second
+      [_, _] = ImageRs.pages_from_file(path, max_alloc: 26)
+      {:error, {:insufficient_memory, _}} = ImageRs.pages_from_file(path, max_alloc: 25)
+    end
+
+    test "pages from single-page image" do
+      [page] = ImageRs.pages_from_file(Path.join(__DIR__, "test.png"))
+      assert [2, 3, 4] == page.shape
+    end
+
+    test "pages from invalid file" do
+      {:error, {:enoent, _}} = ImageRs.pages_from_file(Path.join(__DIR__, "test23.tiff"))
+    end
+  end
+
+  describe "encode image" do
+    test "png with compression and filter" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      pixels = ImageRs.to_binary(image)
+
+      for compression <- [:fast, :default, :best],
+          filter <- [:none, :sub, :up, :avg, :paeth, :adaptive] do
+        png = ImageRs.encode_as(image, :png, compression: compression, filter: filter)
+        %ImageRs{} = decoded = ImageRs.from_binary(png)
+        assert pixels == ImageRs.to_binary(decoded)
+      end
+    end
+
+    test "lossless webp" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      webp = ImageRs.encode_as(image, :webp, lossless: true)
+      %ImageRs{} = decoded = ImageRs.from_binary(webp)
+      assert ImageRs.to_binary(image) == ImageRs.to_binary(decoded)
+    end
+
+    @tag :webp_lossy
+    test "lossy webp" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test_icc.png"))
+      small = ImageRs.encode_as(image, :webp, lossless: false, quality: 10)
+      large = ImageRs.encode_as(image, :webp, lossless: false, quality: 100)
+      assert byte_size(small) < byte_size(large)
+
+      %ImageRs{} = decoded = ImageRs.from_binary(large)
+      assert image.shape == decoded.shape
+      assert ImageRs.icc_profile(image) == ImageRs.icc_profile(decoded)
+    end
+
+    @tag :avif
+    test "avif with speed, quality and threads" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      avif = ImageRs.encode_as(image, :avif, speed: 10, quality: 50, threads: 1)
+      assert <<_size::32, "ftypavif", _rest::binary>> = avif
+    end
+
+    @tag :avif
+    test "avif with out of range options" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      {:error, {:bad_argument, _}} = ImageRs.Nif.encode_as(image, :avif, %{"speed" => "11"})
+      {:error, {:bad_argument, _}} = ImageRs.Nif.encode_as(image, :avif, %{"quality" => "0"})
+      {:error, {:bad_argument, _}} = ImageRs.Nif.encode_as(image, :avif, %{"threads" => "0"})
+    end
+
+    test "avif with invalid options" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      {:error, _} = ImageRs.encode_as(image, :avif, speed: 0)
+      {:error, _} = ImageRs.encode_as(image, :avif, quality: 101)
+      {:error, _} = ImageRs.encode_as(image, :avif, threads: -1)
+    end
+
+    test "webp with invalid options" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      {:error, _} = ImageRs.encode_as(image, :webp, lossless: :maybe)
+      {:error, _} = ImageRs.encode_as(image, :webp, lossless: false, quality: 101)
+
+      {:error, {:bad_argument, _}} =
+        ImageRs.Nif.encode_as(image, :webp, %{"lossless" => "sometimes"})
+    end
+
+    test "png with invalid options" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      {:error, _} = ImageRs.encode_as(image, :png, compression: :huge)
+      {:error, _} = ImageRs.encode_as(image, :png, filter: :median)
+      {:error, {:bad_argument, _}} = ImageRs.Nif.encode_as(image, :png, %{"filter" => "median"})
+    end
+
+    test "multi-resolution ico" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      ico = ImageRs.encode_ico(image, [16, 32, 48, 256])
+      assert <<0::16, 1::little-16, 4::little-16, _rest::binary>> = ico
+
+      %ImageRs{} = decoded = ImageRs.from_binary(ico)
+      assert :ico == decoded.format
+      assert [256, 256, 4] == decoded.shape
+
+      small = ImageRs.resize(image, 16, 16)
+      ico = ImageRs.encode_pages([small, image], :ico)
+      assert <<0::16, 1::little-16, 2::little-16, 16, 16, _rest::binary>> = ico
+
+      {:error, _} = ImageRs.encode_ico(image, [512])
+      {:error, _} = ImageRs.encode_ico(image, [])
+      too_large = ImageRs.resize(image, 300, 300)
+      {:error, {:parameter_error, _}} = ImageRs.Nif.encode_pages([too_large], :ico, %{})
+    end
+
+    test "ico from a non-square image" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      assert [2, 3, 4] == image.shape
+
+      %ImageRs{} = decoded = ImageRs.from_binary(ImageRs.encode_ico(image, [16]))
+      assert [16, 16, 4] == decoded.shape
+      filled = ImageRs.resize_to_fill(image, 16, 16)
+      assert ImageRs.to_binary(filled) == ImageRs.to_binary(decoded)
+    end
+
+    test "jpeg with default quality" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.jpg"))
+      jpeg = ImageRs.encode_as(image, :jpeg)
+      assert <<0xFF, 0xD8, _rest::binary>> = jpeg
+      assert jpeg == ImageRs.Nif.encode_as(image, :jpeg, %{})
+      assert jpeg == ImageRs.encode_as(image, :jpeg, quality: 75)
+    end
+
+    @tag :jpeg_encoder
+    test "jpeg with subsampling, progressive, restart interval and optimized huffman tables" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test_icc.png"))
+
+      jpeg =
+        ImageRs.encode_as(image, :jpeg,
+          quality: 90,
+          subsampling: "4:2:2",
+          progressive: true,
+          restart_interval: 1,
+          optimize_huffman: true
+        )
+
+      # progressive frame header and restart interval marker
+      assert :binary.match(jpeg, <<0xFF, 0xC2>>) != :nomatch
+      assert :binary.match(jpeg, <<0xFF, 0xDD>>) != :nomatch
+
+      %ImageRs{} = decoded = ImageRs.from_binary(jpeg)
+      assert [2, 3, 3] == decoded.shape
+      assert ImageRs.icc_profile(image) == ImageRs.icc_profile(decoded)
+    end
+
+    test "jpeg with options the built-in encoder writes anyway" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.jpg"))
+
+      jpeg =
+        ImageRs.encode_as(image, :jpeg,
+          subsampling: "4:4:4",
+          progressive: false,
+          optimize_huffman: false
+        )
+
+      assert <<0xFF, 0xD8, _rest::binary>> = jpeg
+    end
+
+    test "jpeg with invalid options" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.jpg"))
+      {:error, _} = ImageRs.encode_as(image, :jpeg, quality: 101)
+      {:error, _} = ImageRs.encode_as(image, :jpeg, quality: "high")
+      {:error, _} = ImageRs.encode_as(image, :jpeg, subsampling: "4:1:1")
+      {:error, _} = ImageRs.encode_as(image, :jpeg, progressive: 1)
+      {:error, _} = ImageRs.encode_as(image, :jpeg, restart_interval: 0)
+      {:error, {:bad_argument, _}} = ImageRs.Nif.encode_as(image, :jpeg, %{"quality" => "101"})
+    end
+
+    test "flatten alpha for formats without alpha" do
+      # a transparent red pixel and an opaque blue one
+      %ImageRs{} = image = ImageRs.new(1, 2, :rgba, :u8, <<255, 0, 0, 0, 0, 0, 255, 255>>)
+
+      ppm = ImageRs.encode_as(image, :pnm, subtype: :pixmap, encoding: :binary)
+      assert <<255, 255, 255, 0, 0, 255>> == ImageRs.to_binary(ImageRs.from_binary(ppm))
+
+      ppm =
+        ImageRs.encode_as(image, :pnm,
+          subtype: :pixmap,
+          encoding: :binary,
+          background: {0, 255, 0}
+        )
+
+      assert <<0, 255, 0, 0, 0, 255>> == ImageRs.to_binary(ImageRs.from_binary(ppm))
+
+      %ImageRs{} = png = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      jpeg = ImageRs.encode_as(png, :jpeg, quality: 90)
+      assert [2, 3, 3] == ImageRs.from_binary(jpeg).shape
+
+      {:error, _} = ImageRs.encode_as(image, :jpeg, background: {0, 256, 0})
+      {:error, {:bad_argument, _}} = ImageRs.Nif.encode_as(image, :jpeg, %{"background" => "red"})
+    end
+
+    test "convert color types the format does not support" do
+      %ImageRs{} = image = ImageRs.new(1, 1, :rgb, :u16, <<0, 0, 0x80, 0x80, 255, 255>>)
+      bmp = ImageRs.encode_as(image, :bmp)
+      %ImageRs{} = decoded = ImageRs.from_binary(bmp)
+      assert :u8 == decoded.dtype
+      assert <<0, 128, 255>> == ImageRs.to_binary(decoded)
+
+      data = for value <- [0.0, 0.5, 1.0], into: <<>>, do: <<value::float-32-native>>
+      %ImageRs{} = image = ImageRs.new(1, 1, :rgb, :f32, data)
+      %ImageRs{} = decoded = ImageRs.from_binary(ImageRs.encode_as(image, :png))
+      assert :u16 == decoded.dtype
+
+      %ImageRs{} = gray = ImageRs.grayscale(ImageRs.from_file(Path.join(__DIR__, "test.png")))
+      assert :la == gray.color_type
+      %ImageRs{} = decoded = ImageRs.from_binary(ImageRs.encode_as(gray, :tiff))
+      assert :rgba == decoded.color_type
+    end
+
+    test "convert 16-bit images for pnm subtypes" do
+      %ImageRs{} = rgb16 = ImageRs.new(1, 1, :rgb, :u16, <<0, 0, 0x80, 0x80, 255, 255>>)
+      %ImageRs{} = la16 = ImageRs.new(1, 1, :la, :u16, <<0x80, 0x80, 255, 255>>)
+
+      for image <- [rgb16, la16] do
+        pgm = ImageRs.encode_as(image, :pnm, subtype: :graymap, encoding: :binary)
+        assert <<"P5", _rest::binary>> = pgm
+        %ImageRs{} = decoded = ImageRs.from_binary(pgm)
+        assert {:l, :u8} == {decoded.color_type, decoded.dtype}
+      end
+
+      pam = ImageRs.encode_as(rgb16, :pnm, subtype: :arbitrarymap)
+      %ImageRs{} = decoded = ImageRs.from_binary(pam)
+      assert {:rgb, :u16} == {decoded.color_type, decoded.dtype}
+      assert ImageRs.to_binary(rgb16) == ImageRs.to_binary(decoded)
+
+      pam = ImageRs.encode_as(la16, :pnm, subtype: :arbitrarymap)
+      %ImageRs{} = decoded = ImageRs.from_binary(pam)
+      assert {:rgba, :u16} == {decoded.color_type, decoded.dtype}
+      assert <<0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 255, 255>> == ImageRs.to_binary(decoded)
+    end
+
+    test "exr keeps f32 samples" do
+      data =
+        for value <- [0.5, 1.5, 100.0, -1.0, 0.0, 3.25], into: <<>>, do: <<value::float-32-native>>
+      %ImageRs{} = image = ImageRs.new(1, 2, :rgb, :f32, data)
+
+      exr = ImageRs.encode_as(image, :exr)
+      assert <<0x76, 0x2F, 0x31, 0x01, _rest::binary>> = exr
+      %ImageRs{} = decoded = ImageRs.from_binary(exr)
+      assert :exr == decoded.format
+      assert :f32 == decoded.dtype
+      assert data == ImageRs.to_binary(decoded)
+    end
+
+    test "hdr" do
+      data =
+        for value <- [0.5, 1.5, 100.0, 0.0, 0.25, 3.0], into: <<>>, do: <<value::float-32-native>>
+      %ImageRs{} = image = ImageRs.new(1, 2, :rgb, :f32, data)
+
+      hdr = ImageRs.encode_as(image, :hdr)
+      assert <<"#?RADIANCE", _rest::binary>> = hdr
+      %ImageRs{} = decoded = ImageRs.from_binary(hdr)
+      assert :hdr == decoded.format
+      assert data == ImageRs.to_binary(decoded)
+    end
+
+    test "farbfeld" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      farbfeld = ImageRs.encode_as(image, :farbfeld)
+      assert <<"farbfeld", 3::32, 2::32, _rest::binary>> = farbfeld
+
+      %ImageRs{} = decoded = ImageRs.from_binary(farbfeld)
+      assert :u16 == decoded.dtype
+      assert [2, 3, 4] == decoded.shape
+    end
+
+    test "tiff with compression" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      pixels = ImageRs.to_binary(image)
+
+      for compression <- [:none, :lzw, :deflate, :packbits] do
+        tiff = ImageRs.encode_as(image, :tiff, compression: compression)
+        %ImageRs{} = decoded = ImageRs.from_binary(tiff)
+        assert pixels == ImageRs.to_binary(decoded)
+      end
+    end
+
+    test "tiff with invalid options" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      {:error, _} = ImageRs.encode_as(image, :tiff, compression: :jpeg)
+      {:error, {:bad_argument, _}} =
+        ImageRs.Nif.encode_as(image, :tiff, %{"compression" => "jpeg"})
+    end
+
+    test "multi-page tiff" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      gray = ImageRs.grayscale(ImageRs.from_file(Path.join(__DIR__, "test.jpg")))
+
+      tiff = ImageRs.encode_pages([image, gray], :tiff, compression: :lzw)
+      assert <<"II*\0", _rest::binary>> = tiff
+      %ImageRs{} = first = ImageRs.from_binary(tiff)
+      assert ImageRs.to_binary(image) == ImageRs.to_binary(first)
+
+      {:error, _} = ImageRs.encode_pages([], :tiff)
+      {:error, _} = ImageRs.encode_pages([image, gray], :gif)
+      {:error, {:unsupported_format, _}} = ImageRs.Nif.encode_pages([image], :gif, %{})
+    end
+
+    @tag :tmp_dir
+    test "save with options", %{tmp_dir: tmp_dir} do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      path = Path.join(tmp_dir, "test.jpg")
+
+      :ok = ImageRs.save_with_options(image, path, :jpeg, quality: 80)
+      assert {:ok, ImageRs.encode_as(image, :jpeg, quality: 80)} == File.read(path)
+      assert ["test.jpg"] == File.ls!(tmp_dir)
+
+      {:error, _} = ImageRs.save_with_options(image, path, :jpeg, quality: 101)
+      {:error, {:bad_argument, _}} =
+        ImageRs.Nif.save_with_options(image, path, :jpeg, %{"quality" => "101"})
+
+      {:error, {:enoent, %{message: message}}} =
+        ImageRs.save_with_options(image, Path.join([tmp_dir, "missing", "test.png"]), :png)
+
+      assert is_binary(message)
+
+      assert ["test.jpg"] == File.ls!(tmp_dir)
+    end
+
+    @tag :tmp_dir
+    test "save and encode errors keep their reason", %{tmp_dir: tmp_dir} do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+
+      {:error, {:enoent, _}} = ImageRs.save(image, Path.join([tmp_dir, "missing", "test.png"]))
+      {:error, {:unsupported_format, _}} = ImageRs.save(image, Path.join(tmp_dir, "test.xyz"))
+
+      {:error, {:enoent, _}} =
+        ImageRs.save_with_format(image, Path.join([tmp_dir, "missing", "test"]), :png)
+
+      too_large = ImageRs.resize(image, 300, 300)
+      {:error, {:parameter_error, %{message: message}}} = ImageRs.encode_as(too_large, :ico)
+      assert message =~ "width"
+    end
+  end
+
+  describe "encode animation" do
+    test "gif round trip" do
+      frames = ImageRs.frames_from_file(Path.join(__DIR__, "test_animated.gif"))
+      images = Enum.map(frames, & &1.image)
+      delays = Enum.map(frames, & &1.delay)
+
+      encoded = ImageRs.encode_animation(images, delays, :gif, repeat: 2, speed: 10)
+      assert is_binary(encoded)
+
+      [first, second] = ImageRs.frames_from_binary(encoded)
+      assert {100, 1} == first.delay
+      assert {200, 1} == second.delay
+      assert [2, 3, 4] == second.image.shape
+    end
+
+    test "integer delays" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      encoded = ImageRs.encode_animation([image, image], [50, 50])
+      assert 2 == Enum.count(ImageRs.frames_from_binary(encoded))
+    end
+
+    test "mismatched delays" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      {:error, _} = ImageRs.encode_animation([image, image], [50])
+    end
+
+    test "mismatched frame sizes" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      larger = ImageRs.resize(image, 4, 6)
+
+      {:error, {:invalid_frame, %{message: message}}} =
+        ImageRs.encode_animation([image, larger], [50, 50])
+
+      assert message =~ "frame 1"
+    end
+
+    test "unsupported format" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      {:error, _} = ImageRs.encode_animation([image], [50], :png)
+    end
+  end
+
   describe "interact with nx" do
     test "to_nx" do
       %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
@@ -561,6 +1281,39 @@ defmodule ImageRs.Test do
   end
 
   describe "other image ops" do
+    test "tonemap" do
+      data = for value <- [0.0, 1.0, 3.0], into: <<>>, do: <<value::float-32-native>>
+      %ImageRs{} = image = ImageRs.new(1, 1, :rgb, :f32, data)
+
+      %ImageRs{} = reinhard = ImageRs.tonemap(image, :reinhard, gamma: 1)
+      assert :u8 == reinhard.dtype
+      assert <<0, 128, 191>> == ImageRs.to_binary(reinhard)
+
+      %ImageRs{} = aces = ImageRs.tonemap(image, :aces_filmic, gamma: 1)
+      assert <<0, 205, 243>> == ImageRs.to_binary(aces)
+
+      %ImageRs{} = exposure = ImageRs.tonemap(image, :exposure, exposure: -1, gamma: 1)
+      assert <<0, 128, 255>> == ImageRs.to_binary(exposure)
+
+      %ImageRs{} = gamma = ImageRs.tonemap(image, :reinhard)
+      assert <<0, 186, 224>> == ImageRs.to_binary(gamma)
+    end
+
+    test "tonemap with alpha" do
+      data = for value <- [2.0, 0.0, 1.0, 2.0], into: <<>>, do: <<value::float-32-native>>
+      %ImageRs{} = image = ImageRs.new(1, 1, :rgba, :f32, data)
+      %ImageRs{} = mapped = ImageRs.tonemap(image, :exposure, gamma: 1)
+      assert :rgba == mapped.color_type
+      assert <<255, 0, 255, 255>> == ImageRs.to_binary(mapped)
+    end
+
+    test "tonemap with invalid arguments" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      {:error, {:unsupported_color_type, _}} = ImageRs.tonemap(image)
+      {:error, _} = ImageRs.tonemap(image, :filmic)
+      {:error, _} = ImageRs.tonemap(image, :reinhard, gamma: 0)
+    end
+
     test "grayscale" do
       %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.jpg"))
       assert 3 == image.width
@@ -585,6 +1338,39 @@ defmodule ImageRs.Test do
       # assert <<134, 128, 122, 176, 162, 145>> == data
     end
 
+    test "convert" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+      assert :rgba == image.color_type
+
+      %ImageRs{} = rgb = ImageRs.convert(image, :rgb, :u8)
+      assert [2, 3, 3] == rgb.shape
+      assert :png == rgb.format
+
+      %ImageRs{} = f32 = ImageRs.convert(rgb, :rgba, :f32)
+      assert :rgba == f32.color_type
+      assert :f32 == f32.dtype
+      assert [2, 3, 4] == f32.shape
+
+      %ImageRs{} = back = ImageRs.convert(f32, :rgb, :u8)
+      assert ImageRs.to_binary(rgb) == ImageRs.to_binary(back)
+
+      %ImageRs{} = gray = ImageRs.convert(image, :la, :u16)
+      assert :la == gray.color_type
+      assert :u16 == gray.dtype
+
+      %ImageRs{} = gray = ImageRs.convert(image, :l, :f32)
+      assert :rgb == gray.color_type
+      assert :f32 == gray.dtype
+
+      %ImageRs{} = same = ImageRs.convert(image, :rgba, :u8)
+      assert ImageRs.to_binary(image) == ImageRs.to_binary(same)
+
+      {:error, _} = ImageRs.convert(image, :cmyk, :u8)
+      {:error, _} = ImageRs.convert(image, :rgb, :f64)
+      {:error, {:unsupported_color_type, _}} = ImageRs.Nif.convert(image, :unknown, :u8)
+      {:error, {:unsupported_image_data, _}} = ImageRs.Nif.convert(image, :rgb, :unknown)
+    end
+
     test "invert" do
       %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.jpg"))
       assert 3 == image.width
@@ -767,6 +1553,25 @@ defmodule ImageRs.Test do
       #          151>> == data
     end
 
+    test "apply_orientation" do
+      %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.png"))
+
+      for {orientation, expected} <- [
+            {1, image},
+            {2, ImageRs.fliph(image)},
+            {3, ImageRs.rotate180(image)},
+            {4, ImageRs.flipv(image)},
+            {6, ImageRs.rotate90(image)},
+            {8, ImageRs.rotate270(image)}
+          ] do
+        %ImageRs{} = oriented = ImageRs.apply_orientation(image, orientation)
+        assert expected.shape == oriented.shape
+        assert ImageRs.to_binary(expected) == ImageRs.to_binary(oriented)
+      end
+
+      {:error, _} = ImageRs.apply_orientation(image, 9)
+    end
+
     test "flipv" do
       %ImageRs{} = image = ImageRs.from_file(Path.join(__DIR__, "test.jpg"))
       assert 3 == image.width
@@ -1 +1,4 @@
-ExUnit.start()
+# Tests tagged with a cargo feature that is not enabled by default, such as
+# `:webp_lossy` or `:avif`, need the NIF to be built with that feature. Run them
+# with e.g. `mix test --include avif`.
+ExUnit.start(exclude: [:webp_lossy, :avif, :jpeg_encoder])